@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use crate::error::{parse_all, parse_lines, Error, Result};
+use crate::helper::i32_val;
+use crate::solution::Solution;
+
+pub struct Day1;
+
+impl Solution for Day1 {
+    type Input = Vec<i32>;
+    type Part1 = i32;
+    type Part2 = i32;
+
+    // One signed change per line, +3 or -7
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_lines(input.trim_end(), |l| parse_all(l, i32_val(l.trim())))
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(input))
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        run_2(input)
+    }
+}
+
+fn run_1(changes: &[i32]) -> i32 {
+    changes.iter().sum()
+}
+
+// The frequency after each change of the first pass, starting with 0
+fn frequencies(changes: &[i32]) -> Vec<i32> {
+    let mut freqs = vec![0];
+    for c in changes.iter().take(changes.len().saturating_sub(1)) {
+        freqs.push(freqs[freqs.len() - 1] + c);
+    }
+    freqs
+}
+
+// Every pass shifts all frequencies by the sum, so a frequency can only come back if two
+// frequencies of the first pass are equal modulo the sum
+fn ever_repeats(changes: &[i32]) -> bool {
+    let sum = run_1(changes);
+    if sum == 0 {
+        return !changes.is_empty();
+    }
+    let mut seen = HashSet::new();
+    frequencies(changes)
+        .iter()
+        .any(|f| !seen.insert(f.rem_euclid(sum.abs())))
+}
+
+fn run_2(changes: &[i32]) -> Result<i32> {
+    if !ever_repeats(changes) {
+        return Err(Error::new("no frequency is ever reached twice"));
+    }
+
+    let mut seen = HashSet::new();
+    let mut freq = 0;
+    for c in changes.iter().cycle() {
+        if !seen.insert(freq) {
+            break;
+        }
+        freq += c;
+    }
+    Ok(freq)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aoc1_parse() {
+        assert_eq!(Day1::parse("+1\n-2\n+3\n+1\n"), Ok(vec![1, -2, 3, 1]));
+        assert_eq!(Day1::parse("+1\n-2x\n").unwrap_err().line, Some(2));
+    }
+
+    #[test]
+    fn aoc1_run_1() {
+        assert_eq!(run_1(&[1, -2, 3, 1]), 3);
+        assert_eq!(run_1(&[1, 1, 1]), 3);
+        assert_eq!(run_1(&[1, 1, -2]), 0);
+        assert_eq!(run_1(&[-1, -2, -3]), -6);
+    }
+
+    #[test]
+    fn aoc1_run_2() {
+        assert_eq!(run_2(&[1, -2, 3, 1]), Ok(2));
+        assert_eq!(run_2(&[1, -1]), Ok(0));
+        assert_eq!(run_2(&[3, 3, 4, -2, -4]), Ok(10));
+        assert_eq!(run_2(&[-6, 3, 8, 5, -6]), Ok(5));
+        assert_eq!(run_2(&[7, 7, -2, -7, -4]), Ok(14));
+        assert!(run_2(&[1, 1]).is_err());
+        assert!(run_2(&[]).is_err());
+    }
+}
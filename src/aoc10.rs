@@ -5,13 +5,30 @@ use nom::combinator::opt;
 use nom::multi::many1;
 use nom::*;
 use std::collections::HashSet;
-use std::fs;
 use std::iter::FromIterator;
 use std::ops::AddAssign;
 
-pub fn run() {
-    let input = fs::read_to_string("day10.txt").unwrap();
-    println!("10:1 && 2: {}", run_1(&input));
+use crate::error::{parse_all, Result};
+use crate::solution::Solution;
+
+pub struct Day10;
+
+impl Solution for Day10 {
+    type Input = Vec<Point>;
+    type Part1 = String;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_all(input, points(input))
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(message(input).0)
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        Ok(run_1(input))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -28,7 +45,7 @@ impl AddAssign for Vector {
 }
 
 #[derive(Clone, Debug, PartialEq)]
-struct Point {
+pub struct Point {
     pos: Vector,
     vel: Vector,
 }
@@ -71,10 +88,10 @@ fn points(i: &str) -> IResult<&str, Vec<Point>> {
 // <Vec<Point>>, many1!(point));
 
 fn bounds(points: &[Point]) -> (Vector, Vector) {
-    let mut x_min = i32::max_value();
-    let mut y_min = i32::max_value();
-    let mut x_max = i32::min_value();
-    let mut y_max = i32::min_value();
+    let mut x_min = i32::MAX;
+    let mut y_min = i32::MAX;
+    let mut x_max = i32::MIN;
+    let mut y_max = i32::MIN;
 
     for p in points.iter() {
         x_min = i32::min(x_min, p.pos.x);
@@ -86,20 +103,22 @@ fn bounds(points: &[Point]) -> (Vector, Vector) {
     (Vector { x: x_min, y: y_min }, Vector { x: x_max, y: y_max })
 }
 
-fn print_field((min, max): &(Vector, Vector), points: &[Point]) {
+fn render_field((min, max): &(Vector, Vector), points: &[Point]) -> String {
     let spoints: HashSet<(i32, i32)> =
         HashSet::from_iter(points.iter().map(|p| (p.pos.x, p.pos.y)));
 
+    let mut field = String::new();
     for y in min.y..(max.y + 1) {
+        field.push('\n');
         for x in min.x..(max.x + 1) {
             if spoints.contains(&(x, y)) {
-                print!("*");
+                field.push('*');
             } else {
-                print!(".");
+                field.push('.');
             }
         }
-        println!("");
     }
+    field
 }
 
 fn bounds_area((min, max): &(Vector, Vector)) -> i64 {
@@ -108,18 +127,18 @@ fn bounds_area((min, max): &(Vector, Vector)) -> i64 {
     dx * dy
 }
 
-fn run_1(input: &str) -> u32 {
-    let (_, mut pts) = points(input).unwrap();
+// The message and the number of seconds it took to appear
+fn message(points: &[Point]) -> (String, u32) {
+    let mut pts = points.to_vec();
 
-    let mut last_area = i64::max_value();
+    let mut last_area = i64::MAX;
     let mut last_pts = pts.clone();
 
     for i in 0.. {
         let bounds = bounds(&pts);
         let bounds_area = bounds_area(&bounds);
         if last_area < bounds_area {
-            print_field(&bounds, &last_pts);
-            return i - 1;
+            return (render_field(&bounds, &last_pts), i - 1);
         }
         last_area = bounds_area;
 
@@ -128,7 +147,11 @@ fn run_1(input: &str) -> u32 {
             p.advance();
         }
     }
-    pts.len() as u32
+    unreachable!()
+}
+
+fn run_1(points: &[Point]) -> u32 {
+    message(points).1
 }
 
 #[cfg(test)]
@@ -209,6 +232,12 @@ mod tests {
 
     #[test]
     fn aoc10_run_1() {
-        assert_eq!(run_1(test_input()), 3);
+        assert_eq!(run_1(&Day10::parse(test_input()).unwrap()), 3);
+        assert_eq!(
+            Day10::parse("position=< 9,  1> velocity=< 0,  2>\nposition=< 7,  0> velocity=<-1,  0")
+                .unwrap_err()
+                .line,
+            Some(2)
+        );
     }
 }
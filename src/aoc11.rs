@@ -1,8 +1,31 @@
 use rayon::prelude::*;
 
-pub fn run() {
-    println!("11:1: {:?}", run_1(2866, 3));
-    println!("11:2: {:?}", run_2(2866));
+use crate::error::{Error, Result};
+use crate::solution::Solution;
+
+pub struct Day11;
+
+impl Solution for Day11 {
+    type Input = i32;
+    type Part1 = String;
+    type Part2 = String;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        input
+            .trim()
+            .parse()
+            .map_err(|_| Error::at_line(1, format!("expected a number, found '{}'", input.trim())))
+    }
+
+    fn part_one(serial: &Self::Input) -> Result<Self::Part1> {
+        let (_, p) = run_1(*serial, 3);
+        Ok(format!("{},{}", p.x, p.y))
+    }
+
+    fn part_two(serial: &Self::Input) -> Result<Self::Part2> {
+        let (_, size, p) = run_2(*serial);
+        Ok(format!("{},{},{}", p.x, p.y, size))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -24,10 +47,16 @@ fn power_level(serial: i32, p: &Point) -> i64 {
 }
 
 fn create_power_map(serial: i32) -> Vec<Vec<i64>> {
-    let mut power = vec![vec![0;300]; 300];
+    let mut power = vec![vec![0; 300]; 300];
     for y in 1..301 {
         for x in 1..301 {
-            power[y-1][x-1] = power_level(serial, &Point { x: x as i32, y: y as i32 });
+            power[y - 1][x - 1] = power_level(
+                serial,
+                &Point {
+                    x: x as i32,
+                    y: y as i32,
+                },
+            );
         }
     }
     power
@@ -38,20 +67,31 @@ fn run_1(serial: i32, size: usize) -> (i64, Point) {
     run_inner(size, &power)
 }
 
-fn run_inner(size: usize, power: &Vec<Vec<i64>>) -> (i64, Point) {
+// sums[y][x] is the sum of all power levels above and to the left of (x, y)
+fn create_sum_map(power: &[Vec<i64>]) -> Vec<Vec<i64>> {
+    let mut sums = vec![vec![0; 301]; 301];
+    for y in 1..301 {
+        for x in 1..301 {
+            sums[y][x] = power[y - 1][x - 1] + sums[y - 1][x] + sums[y][x - 1] - sums[y - 1][x - 1];
+        }
+    }
+    sums
+}
+
+fn run_inner(size: usize, power: &[Vec<i64>]) -> (i64, Point) {
+    let sums = create_sum_map(power);
     let mut max_power = 0;
     let mut max_pt = Point { x: 1, y: 1 };
-    for y in 1..(301-size) {
-        for x in 1..(301-size) {
-            let mut sum = 0;
-            for dy in y..(y+size) {
-                for dx in x..(x+size) {
-                    sum += power[dy-1][dx-1];
-                }
-            }
+    for y in 1..(301 - size) {
+        for x in 1..(301 - size) {
+            let (x0, y0, x1, y1) = (x - 1, y - 1, x + size - 1, y + size - 1);
+            let sum = sums[y1][x1] - sums[y0][x1] - sums[y1][x0] + sums[y0][x0];
             if sum > max_power {
                 max_power = sum;
-                max_pt = Point { x: x as i32, y: y as i32 }
+                max_pt = Point {
+                    x: x as i32,
+                    y: y as i32,
+                }
             }
         }
     }
@@ -63,9 +103,11 @@ fn run_2(serial: i32) -> (i64, usize, Point) {
 
     let sizes: Vec<usize> = (1..301).collect();
 
-    let powers = sizes.par_iter().map(|size| (*size, run_inner(*size, &power)));
+    let powers = sizes
+        .par_iter()
+        .map(|size| (*size, run_inner(*size, &power)));
 
-    let max = powers.max_by(|a, b| ((a.1).0.cmp(&(b.1).0))).unwrap();
+    let max = powers.max_by(|a, b| (a.1).0.cmp(&(b.1).0)).unwrap();
 
     ((max.1).0, max.0, (max.1).1)
 }
@@ -84,23 +126,16 @@ mod tests {
 
     #[test]
     fn aoc11_run_1() {
-        assert_eq!(run_1(18, 3),
-                   (29, Point { x: 33, y: 45 }));
-        assert_eq!(run_1(42, 3),
-                    (30, Point { x: 21, y: 61}));
+        assert_eq!(run_1(18, 3), (29, Point { x: 33, y: 45 }));
+        assert_eq!(run_1(42, 3), (30, Point { x: 21, y: 61 }));
     }
 
     #[test]
     fn aoc11_run_2() {
-        assert_eq!(run_1(18, 16),
-                   (113, Point { x: 90, y: 269 }));
-        assert_eq!(run_1(42, 12),
-                    (119, Point { x: 232, y: 251}));
-
-        assert_eq!(run_2(18),
-                   (113, 16, Point { x: 90, y: 269 }));
-        assert_eq!(run_2(42),
-                    (119, 12, Point { x: 232, y: 251}));
-    }
+        assert_eq!(run_1(18, 16), (113, Point { x: 90, y: 269 }));
+        assert_eq!(run_1(42, 12), (119, Point { x: 232, y: 251 }));
 
+        assert_eq!(run_2(18), (113, 16, Point { x: 90, y: 269 }));
+        assert_eq!(run_2(42), (119, 12, Point { x: 232, y: 251 }));
+    }
 }
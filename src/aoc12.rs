@@ -1,4 +1,3 @@
-use std::fs;
 use std::ops::{Index, IndexMut};
 
 use nom::branch::alt;
@@ -9,9 +8,27 @@ use nom::combinator::opt;
 use nom::multi::{many1, many_m_n};
 use nom::*;
 
-pub fn run() {
-    let input = fs::read_to_string("day12.txt").unwrap();
-    println!("12:1 {}", run_1(&input, 20));
+use crate::error::{parse_all, Result};
+use crate::solution::{Solution, Unsolved};
+
+pub struct Day12;
+
+impl Solution for Day12 {
+    type Input = Pots;
+    type Part1 = isize;
+    type Part2 = Unsolved;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_all(input, parse(input))
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(input, 20))
+    }
+
+    fn part_two(_input: &Self::Input) -> Result<Self::Part2> {
+        Ok(Unsolved)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -30,8 +47,10 @@ impl Rule {
     }
 }
 
-#[derive(Debug)]
-struct OffsetVec<T> {
+// A growable vector that can be indexed with any isize, reading outside of the stored range
+// gives the default value and writing outside of it grows the vector in that direction.
+#[derive(Debug, Clone)]
+pub struct OffsetVec<T> {
     offset: isize,
     default: T,
     vec: Vec<T>,
@@ -41,17 +60,26 @@ impl<T> OffsetVec<T>
 where
     T: Clone,
 {
-    fn new(init: &[T], default: T) -> Self {
+    pub fn new(init: &[T], default: T) -> Self {
         OffsetVec {
             offset: 0,
-            default: default,
+            default,
             vec: init.to_vec(),
         }
     }
 
-    fn len(&self) -> usize {
+    pub fn len(&self) -> usize {
         self.vec.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    // The lowest index that is stored
+    pub fn offset(&self) -> isize {
+        self.offset
+    }
 }
 
 impl<T> Index<isize> for OffsetVec<T>
@@ -93,16 +121,13 @@ where
 }
 
 #[derive(Debug)]
-struct Input {
+pub struct Pots {
     initial_state: OffsetVec<bool>,
     rules: Vec<Rule>,
 }
 
 fn boolean(i: &str) -> IResult<&str, bool> {
-    nom::combinator::map(alt((tag("."), tag("#"))), |t| match t {
-        "." => false,
-        _ => true,
-    })(i)
+    nom::combinator::map(alt((tag("."), tag("#"))), |t| t == "#")(i)
     // let (i, t) = alt((tag("."), tag("#")))(i)?;
     // Ok((
     //     i,
@@ -129,16 +154,7 @@ fn rule(i: &str) -> IResult<&str, Rule> {
     Ok((i, Rule { mtch: m, output: o }))
 }
 
-fn create_vec(init: &[bool]) -> Vec<bool> {
-    let mut res = vec![false; init.len() + 2];
-
-    res[2..].copy_from_slice(init);
-    res.push(false);
-    res.push(false);
-    res
-}
-
-fn parse(i: &str) -> IResult<&str, Input> {
+fn parse(i: &str) -> IResult<&str, Pots> {
     let (i, _) = tag("initial state:")(i)?;
     let (i, _) = space1(i)?;
     let (i, initial_state) = many1(boolean)(i)?;
@@ -148,49 +164,30 @@ fn parse(i: &str) -> IResult<&str, Input> {
     let (i, rules) = many1(rule)(i)?;
     Ok((
         i,
-        Input {
+        Pots {
             initial_state: OffsetVec::new(&initial_state, false),
-            rules: rules,
+            rules,
         },
     ))
 }
 
-fn print_state(state: &[bool]) {
-    for i in 2..(state.len() - 2) {
-        if state[i] {
-            print!("#");
-        } else {
-            print!(".");
+fn run_1(pots: &Pots, iterations: usize) -> isize {
+    let mut state = pots.initial_state.clone();
+    for _ in 0..iterations {
+        let mut new_state = OffsetVec::new(&[], false);
+        // A pot can only change if it is within two steps of a planted pot
+        for i in (state.offset - 2)..(state.offset + state.len() as isize + 2) {
+            let window: Vec<bool> = ((i - 2)..(i + 3)).map(|j| state[j]).collect();
+            if pots.rules.iter().any(|r| r.output && r.is_match(&window)) {
+                new_state[i] = true;
+            }
         }
+        state = new_state;
     }
-    println!("");
-}
 
-fn run_1(_input_str: &str, _iterations: usize) -> usize {
-    // let (_, input) = parse(input_str)).unwrap();
-    // let mut state = input.initial_state;
-    // for _ in 0..iterations {
-    //     let mut new_v = state.clone();
-    //     for i in 2..(state.len()-2) {
-    //         for r in &input.rules {
-    //             if r.is_match(&state[i-2..i+3]) {
-    //                 new_v[i+2] = r.output;
-    //                 break;
-    //             }
-    //         }
-    //     }
-    //     state = new_v;
-    //     print_state(&state);
-    // }
-
-    let mut idx_sum = 0;
-    // for (i,v) in state[2..].iter().enumerate() {
-    //     if *v {
-    //         println!("i = {}", i);
-    //         idx_sum += i;
-    //     }
-    // }
-    idx_sum
+    (state.offset..(state.offset + state.len() as isize))
+        .filter(|i| state[*i])
+        .sum()
 }
 
 #[cfg(test)]
@@ -249,9 +246,9 @@ mod tests {
         assert_eq!(parse_res.0, "");
         assert_eq!(parse_res.1.rules.len(), 14);
         assert_eq!(parse_res.1.initial_state.len(), 25);
-        assert_eq!(parse_res.1.initial_state[0], true);
-        assert_eq!(parse_res.1.initial_state[1], false);
-        assert_eq!(parse_res.1.initial_state[24], true);
+        assert!(parse_res.1.initial_state[0]);
+        assert!(!parse_res.1.initial_state[1]);
+        assert!(parse_res.1.initial_state[24]);
     }
 
     #[test]
@@ -271,13 +268,19 @@ mod tests {
         assert_eq!(v.len(), 5);
         assert_eq!(v.vec, [true, false, true, false, true]);
 
-        assert_eq!(v[-3], false);
-        assert_eq!(v[2], true);
-        assert_eq!(v[3], false);
+        assert!(!v[-3]);
+        assert!(v[2]);
+        assert!(!v[3]);
     }
 
     #[test]
     fn aoc12_run_1() {
-        assert_eq!(run_1(init_data(), 20), 325);
+        assert_eq!(run_1(&Day12::parse(init_data()).unwrap(), 20), 325);
+        assert_eq!(
+            Day12::parse("initial state: #..#\n\n...## => x")
+                .unwrap_err()
+                .line,
+            Some(3)
+        );
     }
 }
@@ -1,7 +1,9 @@
 use std::cmp::Ordering;
-use std::fs;
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::error::{Error, Result};
+use crate::solution::Solution;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Direction {
     Left,
     Up,
@@ -40,7 +42,7 @@ enum Position {
     Intersection,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 struct Cart {
     dir: Direction,
     intersect_dir: usize,
@@ -50,11 +52,7 @@ struct Cart {
 
 impl PartialOrd for Cart {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        if self.y == other.y {
-            self.x.partial_cmp(&other.x)
-        } else {
-            self.y.partial_cmp(&other.y)
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -78,8 +76,11 @@ impl Cart {
         }
     }
 
-    fn mv(&mut self, map: &Map) {
-        match map[self.y as usize][self.x as usize] {
+    fn mv(&mut self, map: &Map) -> Result<()> {
+        let pos = map
+            .get(self.y as usize)
+            .and_then(|row| row.get(self.x as usize));
+        match pos.cloned().flatten() {
             Some(Position::Intersection) => match self.intersect_dir {
                 0 => {
                     let (x, y) = self.next_pos(&self.dir.left());
@@ -141,17 +142,27 @@ impl Cart {
             }
 
             None => {
-                dbg! { &self};
-                dbg! { &map[self.y as usize - 1][self.x as usize ]};
-                unreachable!();
+                return Err(Error {
+                    line: Some(self.y as usize + 1),
+                    column: Some(self.x as usize + 1),
+                    ..Error::new("cart ran off the track")
+                })
             }
         }
+        Ok(())
     }
 }
 
 type Map = Vec<Vec<Option<Position>>>;
 
-fn parse_map(input: &str) -> (Map, Vec<Cart>) {
+// The tracks and the carts in their starting positions
+#[derive(Debug)]
+pub struct Tracks {
+    map: Map,
+    carts: Vec<Cart>,
+}
+
+fn parse_map(input: &str) -> Result<(Map, Vec<Cart>)> {
     let mut max_width = 0;
     let mut map = Map::new();
     let mut carts = Vec::new();
@@ -216,8 +227,11 @@ fn parse_map(input: &str) -> (Map, Vec<Cart>) {
                 ' ' => row.push(None),
 
                 any => {
-                    dbg! {any};
-                    unreachable!()
+                    return Err(Error {
+                        line: Some(y + 1),
+                        column: Some(x + 1),
+                        ..Error::new(format!("unexpected '{}' in track", any))
+                    })
                 }
             }
             last = row[row.len() - 1].clone();
@@ -235,9 +249,10 @@ fn parse_map(input: &str) -> (Map, Vec<Cart>) {
         }
     }
 
-    (map, carts)
+    Ok((map, carts))
 }
 
+#[cfg(test)]
 fn printmap(m: &Map, carts: &[Cart]) {
     for (y, row) in m.iter().enumerate() {
         for (x, col) in row.iter().enumerate() {
@@ -269,46 +284,68 @@ fn printmap(m: &Map, carts: &[Cart]) {
     println!();
 }
 
-pub fn run() {
-    let input = fs::read_to_string("day13.txt").unwrap();
-    let (x, y) = run_1(&input);
-    println!("13:1 {},{}", x, y);
-    let (x, y) = run_2(&input);
-    println!("13:2 {},{}", x, y);
+pub struct Day13;
+
+impl Solution for Day13 {
+    type Input = Tracks;
+    type Part1 = String;
+    type Part2 = String;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        let (map, carts) = parse_map(input)?;
+        Ok(Tracks { map, carts })
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        let (x, y) = run_1(input)?;
+        Ok(format!("{},{}", x, y))
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        let (x, y) = run_2(input)?;
+        Ok(format!("{},{}", x, y))
+    }
 }
 
-fn run_1(input: &str) -> (isize, isize) {
-    let (map, mut carts) = parse_map(input);
+fn run_1(tracks: &Tracks) -> Result<(isize, isize)> {
+    let map = &tracks.map;
+    let mut carts = tracks.carts.clone();
+    if carts.len() < 2 {
+        return Err(Error::new("at least two carts are needed for a crash"));
+    }
     loop {
         // First sort the carts per their position
         carts.sort();
 
         for i in 0..carts.len() {
-            carts[i].mv(&map);
+            carts[i].mv(map)?;
             for j in 0..carts.len() {
                 if i != j && (carts[i].x, carts[i].y) == (carts[j].x, carts[j].y) {
                     // printmap(&map, &carts);
-                    return (carts[i].x, carts[i].y);
+                    return Ok((carts[i].x, carts[i].y));
                 }
             }
         }
     }
 }
 
-fn run_2(input: &str) -> (isize, isize) {
-    let (map, mut carts) = parse_map(input);
+fn run_2(tracks: &Tracks) -> Result<(isize, isize)> {
+    let map = &tracks.map;
+    let mut carts = tracks.carts.clone();
     loop {
         // First sort the carts per their position
         carts.sort();
 
-        if carts.len() == 1 {
-            break;
+        match carts.len() {
+            0 => return Err(Error::new("every cart crashed, there is no last cart")),
+            1 => break,
+            _ => {}
         }
 
         let mut to_remove = Vec::new();
 
         for i in 0..carts.len() {
-            carts[i].mv(&map);
+            carts[i].mv(map)?;
             for j in 0..carts.len() {
                 if i != j && (carts[i].x, carts[i].y) == (carts[j].x, carts[j].y) {
                     to_remove.push(i);
@@ -322,7 +359,7 @@ fn run_2(input: &str) -> (isize, isize) {
             carts.remove(i);
         }
     }
-    (carts[0].x, carts[0].y)
+    Ok((carts[0].x, carts[0].y))
 }
 
 #[cfg(test)]
@@ -339,16 +376,16 @@ mod tests {
 
     #[test]
     fn aoc13_advance() {
-        let (m, mut c) = parse_map(map_1());
+        let (m, mut c) = parse_map(map_1()).unwrap();
         // dbg! {&m};
-        c.iter_mut().for_each(|c| c.mv(&m));
+        c.iter_mut().for_each(|c| c.mv(&m).unwrap());
         assert_eq!((3, 0), (c[0].x, c[0].y));
         assert_eq!(Direction::Right, c[0].dir);
         assert_eq!((9, 4), (c[1].x, c[1].y));
         assert_eq!(Direction::Down, c[1].dir);
 
-        c.iter_mut().for_each(|c| c.mv(&m));
-        c.iter_mut().for_each(|c| c.mv(&m));
+        c.iter_mut().for_each(|c| c.mv(&m).unwrap());
+        c.iter_mut().for_each(|c| c.mv(&m).unwrap());
         printmap(&m, &c);
         assert_eq!((4, 1), (c[0].x, c[0].y));
         assert_eq!(Direction::Down, c[0].dir);
@@ -358,10 +395,14 @@ mod tests {
 
     #[test]
     fn aoc13_1() {
-        let (m, c) = parse_map(map_1());
+        let (m, c) = parse_map(map_1()).unwrap();
         printmap(&m, &c);
         assert_eq!(2, c.len());
-        assert_eq!((7, 3), run_1(map_1()));
+        assert_eq!(Ok((7, 3)), run_1(&Day13::parse(map_1()).unwrap()));
+
+        let e = Day13::parse("->-<-\n-x").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(2), Some(2)));
+        assert!(run_1(&Day13::parse("-->--").unwrap()).is_err());
     }
 
     fn map_2() -> &'static str {
@@ -376,9 +417,9 @@ mod tests {
 
     #[test]
     fn aoc13_2() {
-        let (m, c) = parse_map(map_2());
+        let (m, c) = parse_map(map_2()).unwrap();
         printmap(&m, &c);
         assert_eq!(9, c.len());
-        assert_eq!((6, 4), run_2(map_2()));
+        assert_eq!(Ok((6, 4)), run_2(&Day13::parse(map_2()).unwrap()));
     }
 }
@@ -1,3 +1,6 @@
+use crate::error::{Error, Result};
+use crate::solution::Solution;
+
 #[derive(Debug)]
 struct State {
     elf_1_pos: usize,
@@ -26,9 +29,31 @@ impl State {
     }
 }
 
-pub fn run() {
-    println!("14:1 - {}", run_1(990941));
-    println!("14:2 - {}", run_2(990941));
+pub struct Day14;
+
+impl Solution for Day14 {
+    type Input = usize;
+    type Part1 = String;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        input
+            .trim()
+            .parse()
+            .map_err(|_| Error::at_line(1, format!("expected a number, found '{}'", input.trim())))
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        // The score is ten digits, keep any leading zeros
+        Ok(format!("{:010}", run_1(*input)))
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        if *input == 0 {
+            return Err(Error::new("the recipe pattern must not be empty"));
+        }
+        Ok(run_2(*input))
+    }
 }
 
 fn run_1(num_recipies: usize) -> usize {
@@ -82,7 +107,7 @@ fn sum_to_vec(sum: u8) -> Vec<u8> {
     }
 }
 
-fn score(recipies: &Vec<u8>, num_recipies: usize) -> usize {
+fn score(recipies: &[u8], num_recipies: usize) -> usize {
     recipies[num_recipies..num_recipies + 10]
         .iter()
         .fold(0, |acc, score| (acc * 10) + *score as usize)
@@ -131,8 +156,8 @@ mod tests {
 
     #[test]
     fn aoc14_score() {
-        assert_eq!(9876543210, score(&vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0], 0));
-        assert_eq!(123456789, score(&vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9], 0));
+        assert_eq!(9876543210, score(&[9, 8, 7, 6, 5, 4, 3, 2, 1, 0], 0));
+        assert_eq!(123456789, score(&[0, 1, 2, 3, 4, 5, 6, 7, 8, 9], 0));
         assert_eq!(
             5158916779,
             score(
@@ -145,7 +170,7 @@ mod tests {
     #[test]
     fn aoc14_run_1() {
         assert_eq!(5158916779, run_1(9));
-        assert_eq!(0124515891, run_1(5));
+        assert_eq!(124515891, run_1(5));
         assert_eq!(9251071085, run_1(18));
         assert_eq!(5941429882, run_1(2018));
     }
@@ -153,7 +178,7 @@ mod tests {
     #[test]
     fn aoc14_run_2() {
         assert_eq!(9, run_2(51589));
-        assert_eq!(6, run_2(01245));
+        assert_eq!(6, run_2(1245));
         assert_eq!(18, run_2(92510));
         assert_eq!(2018, run_2(59414));
     }
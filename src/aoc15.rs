@@ -1,22 +1,468 @@
-use std::fs;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-pub fn run() {
-    let input = fs::read_to_string("day15.txt").unwrap();
-    println!("15 {}", input.len());
+use crate::error::{Error, Result};
+use crate::solution::Solution;
+
+pub struct Day15;
+
+impl Solution for Day15 {
+    type Input = Cave;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        Cave::parse(input)
+    }
+
+    fn part_one(cave: &Self::Input) -> Result<Self::Part1> {
+        run_1(cave)
+    }
+
+    fn part_two(cave: &Self::Input) -> Result<Self::Part2> {
+        run_2(cave)
+    }
+}
+
+// (y, x), so that positions compare in reading order
+type Pos = (usize, usize);
+
+const HIT_POINTS: i32 = 200;
+const ATTACK_POWER: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Race {
+    Elf,
+    Goblin,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Unit {
+    race: Race,
+    pos: Pos,
+    hp: i32,
+    attack: i32,
+}
+
+impl Unit {
+    fn alive(&self) -> bool {
+        self.hp > 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Square {
+    Wall,
+    Open,
+    // Index into Cave::units
+    Unit(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Cave {
+    map: Vec<Vec<Square>>,
+    units: Vec<Unit>,
+    elf_deaths: usize,
+}
+
+#[derive(Debug, PartialEq)]
+enum Round {
+    Complete,
+    // Complete, but no unit moved or attacked, so nothing will ever change
+    Idle,
+    // A unit found no targets
+    Ended,
+}
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    rounds: usize,
+    hp_left: i32,
+    elf_deaths: usize,
+}
+
+impl Outcome {
+    fn score(&self) -> usize {
+        self.rounds * self.hp_left as usize
+    }
+}
+
+// Adjacent positions in reading order
+fn neighbours((y, x): Pos) -> impl Iterator<Item = Pos> {
+    let up = y.checked_sub(1).map(|y| (y, x));
+    let left = x.checked_sub(1).map(|x| (y, x));
+    up.into_iter()
+        .chain(left)
+        .chain(vec![(y, x + 1), (y + 1, x)])
 }
 
+impl Cave {
+    fn parse(input: &str) -> Result<Self> {
+        let mut map = Vec::new();
+        let mut units = Vec::new();
+        for (y, line) in input.lines().enumerate() {
+            let mut row = Vec::new();
+            for (x, c) in line.trim_end().chars().enumerate() {
+                let square = match c {
+                    '#' => Square::Wall,
+                    '.' => Square::Open,
+                    'E' | 'G' => {
+                        units.push(Unit {
+                            race: if c == 'E' { Race::Elf } else { Race::Goblin },
+                            pos: (y, x),
+                            hp: HIT_POINTS,
+                            attack: ATTACK_POWER,
+                        });
+                        Square::Unit(units.len() - 1)
+                    }
+                    _ => {
+                        return Err(Error {
+                            line: Some(y + 1),
+                            column: Some(x + 1),
+                            ..Error::new(format!("unexpected '{}' in map", c))
+                        })
+                    }
+                };
+                row.push(square);
+            }
+            map.push(row);
+        }
+        Ok(Cave {
+            map,
+            units,
+            elf_deaths: 0,
+        })
+    }
+
+    // The map as in the puzzle text, used to check rounds against the examples
+    #[cfg(test)]
+    fn render(&self) -> String {
+        let mut s = String::new();
+        for row in self.map.iter() {
+            for square in row.iter() {
+                s.push(match square {
+                    Square::Wall => '#',
+                    Square::Open => '.',
+                    Square::Unit(i) if self.units[*i].race == Race::Elf => 'E',
+                    Square::Unit(_) => 'G',
+                });
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    // Everything outside of the map is wall
+    fn get(&self, (y, x): Pos) -> Square {
+        self.map
+            .get(y)
+            .and_then(|row| row.get(x))
+            .cloned()
+            .unwrap_or(Square::Wall)
+    }
+
+    fn with_elf_attack(&self, attack: i32) -> Self {
+        let mut cave = self.clone();
+        for u in cave.units.iter_mut().filter(|u| u.race == Race::Elf) {
+            u.attack = attack;
+        }
+        cave
+    }
+
+    // Shortest distance from `from` to every reachable open square
+    fn distances(&self, from: Pos) -> HashMap<Pos, usize> {
+        let mut dist = HashMap::new();
+        let mut queue = VecDeque::new();
+        dist.insert(from, 0);
+        queue.push_back(from);
+        while let Some(p) = queue.pop_front() {
+            let d = dist[&p];
+            for n in neighbours(p) {
+                if self.get(n) == Square::Open && !dist.contains_key(&n) {
+                    dist.insert(n, d + 1);
+                    queue.push_back(n);
+                }
+            }
+        }
+        dist
+    }
+
+    // The adjacent enemy with the fewest hit points, ties broken in reading order
+    fn enemy_in_range(&self, unit: usize) -> Option<usize> {
+        let race = self.units[unit].race;
+        neighbours(self.units[unit].pos)
+            .filter_map(|p| match self.get(p) {
+                Square::Unit(i) if self.units[i].race != race => Some(i),
+                _ => None,
+            })
+            .min_by_key(|i| (self.units[*i].hp, self.units[*i].pos))
+    }
+
+    // Move towards the nearest square in range of an enemy, ties are broken in reading order,
+    // both for the square to move to and the first step to take
+    fn next_step(&self, unit: usize) -> Option<Pos> {
+        let Unit { race, pos, .. } = self.units[unit];
+        let in_range: HashSet<Pos> = self
+            .units
+            .iter()
+            .filter(|u| u.alive() && u.race != race)
+            .flat_map(|u| neighbours(u.pos))
+            .filter(|p| self.get(*p) == Square::Open)
+            .collect();
+
+        let dist = self.distances(pos);
+        let (_, chosen) = in_range
+            .iter()
+            .filter_map(|p| dist.get(p).map(|d| (*d, *p)))
+            .min()?;
+
+        let back = self.distances(chosen);
+        neighbours(pos)
+            .filter_map(|p| back.get(&p).map(|d| (*d, p)))
+            .min()
+            .map(|(_, p)| p)
+    }
+
+    fn round(&mut self) -> Round {
+        let mut order: Vec<usize> = (0..self.units.len())
+            .filter(|i| self.units[*i].alive())
+            .collect();
+        order.sort_by_key(|i| self.units[*i].pos);
+
+        let mut changed = false;
+        for i in order {
+            // Killed earlier in the round
+            if !self.units[i].alive() {
+                continue;
+            }
+            let race = self.units[i].race;
+            if !self.units.iter().any(|u| u.alive() && u.race != race) {
+                return Round::Ended;
+            }
+
+            if self.enemy_in_range(i).is_none() {
+                if let Some(step) = self.next_step(i) {
+                    let (y, x) = self.units[i].pos;
+                    self.map[y][x] = Square::Open;
+                    self.map[step.0][step.1] = Square::Unit(i);
+                    self.units[i].pos = step;
+                    changed = true;
+                }
+            }
+
+            if let Some(t) = self.enemy_in_range(i) {
+                self.units[t].hp -= self.units[i].attack;
+                if !self.units[t].alive() {
+                    let (y, x) = self.units[t].pos;
+                    self.map[y][x] = Square::Open;
+                    if self.units[t].race == Race::Elf {
+                        self.elf_deaths += 1;
+                    }
+                }
+                changed = true;
+            }
+        }
+
+        if changed {
+            Round::Complete
+        } else {
+            Round::Idle
+        }
+    }
+
+    // Fights until one side is gone, or until the first elf dies if `elves_must_live`
+    fn fight(mut self, elves_must_live: bool) -> Result<Outcome> {
+        let mut rounds = 0;
+        loop {
+            let round = self.round();
+            if round == Round::Ended || (elves_must_live && self.elf_deaths > 0) {
+                break;
+            }
+            if round == Round::Idle {
+                return Err(Error::new(format!(
+                    "the units can not reach each other, the battle never ends after round {}",
+                    rounds
+                )));
+            }
+            rounds += 1;
+        }
+
+        Ok(Outcome {
+            rounds,
+            hp_left: self.units.iter().filter(|u| u.alive()).map(|u| u.hp).sum(),
+            elf_deaths: self.elf_deaths,
+        })
+    }
+}
+
+fn run_1(cave: &Cave) -> Result<usize> {
+    Ok(cave.clone().fight(false)?.score())
+}
+
+// Fewer elves can die with more attack power, but it is not monotonic in the number of rounds,
+// so try every attack power in turn. With 200 every goblin dies from a single hit.
+fn run_2(cave: &Cave) -> Result<usize> {
+    for attack in (ATTACK_POWER + 1)..=HIT_POINTS {
+        let outcome = cave.with_elf_attack(attack).fight(true)?;
+        if outcome.elf_deaths == 0 {
+            return Ok(outcome.score());
+        }
+    }
+    Err(Error::new("the elves can not win without losses"))
+}
 
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
+
+    fn cave(map: &str) -> Cave {
+        Cave::parse(map).unwrap()
+    }
+
     #[test]
     fn aoc15_parse() {
-        // let map = r#"#######
-// #.G...#
-// #...EG#
-// #.#.#G#
-// #..G#E#
-// #.....#
-// #######  "#;
+        let c = cave("#######\n#.G.E.#\n#######\n");
+        assert_eq!(c.units.len(), 2);
+        assert_eq!(c.units[0].race, Race::Goblin);
+        assert_eq!(c.units[1].pos, (1, 4));
+        assert_eq!(c.get((1, 2)), Square::Unit(0));
+        assert_eq!(c.get((10, 10)), Square::Wall);
+        assert_eq!(c.render(), "#######\n#.G.E.#\n#######\n");
+
+        let e = Cave::parse("####\n#.x#\n").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(2), Some(3)));
+    }
+
+    #[test]
+    fn aoc15_move() {
+        let c = cave("#######\n#E..G.#\n#...#.#\n#.G.#G#\n#######\n");
+        assert_eq!(c.next_step(0), Some((1, 2)));
+
+        let c = cave("#######\n#.E...#\n#.....#\n#...G.#\n#######\n");
+        assert_eq!(c.next_step(0), Some((1, 3)));
+
+        let mut c = cave(
+            "#########
+#G..G..G#
+#.......#
+#.......#
+#G..E..G#
+#.......#
+#.......#
+#G..G..G#
+#########",
+        );
+        for _ in 0..3 {
+            c.round();
+        }
+        assert_eq!(
+            c.render(),
+            "#########
+#.......#
+#..GGG..#
+#..GEG..#
+#G..G...#
+#......G#
+#.......#
+#.......#
+#########
+"
+        );
+    }
+
+    // The example battles with their outcome and the outcome with the lowest elf attack power
+    // where no elf dies
+    const EXAMPLES: [(&str, usize, Option<usize>); 6] = [
+        (
+            "#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######",
+            27730,
+            Some(4988),
+        ),
+        (
+            "#######
+#G..#E#
+#E#E.E#
+#G.##.#
+#...#E#
+#...E.#
+#######",
+            36334,
+            None,
+        ),
+        (
+            "#######
+#E..EG#
+#.#G.E#
+#E.##E#
+#G..#.#
+#..E#.#
+#######",
+            39514,
+            Some(31284),
+        ),
+        (
+            "#######
+#E.G#.#
+#.#G..#
+#G.#.G#
+#G..#.#
+#...E.#
+#######",
+            27755,
+            Some(3478),
+        ),
+        (
+            "#######
+#.E...#
+#.#..G#
+#.###.#
+#E#G#G#
+#...#G#
+#######",
+            28944,
+            Some(6474),
+        ),
+        (
+            "#########
+#G......#
+#.E.#...#
+#..##..G#
+#...##..#
+#...#...#
+#.G...G.#
+#.....G.#
+#########",
+            18740,
+            Some(1140),
+        ),
+    ];
+
+    #[test]
+    fn aoc15_run_1() {
+        let outcome = cave(EXAMPLES[0].0).fight(false).unwrap();
+        assert_eq!((outcome.rounds, outcome.hp_left), (47, 590));
+
+        for (map, score, _) in EXAMPLES.iter() {
+            assert_eq!(run_1(&cave(map)), Ok(*score));
+        }
+
+        assert!(run_1(&cave("#####\n#E#G#\n#####")).is_err());
+    }
+
+    #[test]
+    fn aoc15_run_2() {
+        let outcome = cave(EXAMPLES[0].0).with_elf_attack(15).fight(true).unwrap();
+        assert_eq!((outcome.rounds, outcome.hp_left), (29, 172));
+
+        for (map, _, score) in EXAMPLES.iter() {
+            if let Some(score) = score {
+                assert_eq!(run_2(&cave(map)), Ok(*score));
+            }
+        }
     }
 }
@@ -1,45 +1,52 @@
-use std::collections::HashMap;
-use std::fs;
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
 
 use super::helper::usize_val;
 use super::opcodes::*;
+use crate::asm::{self, Mapping, SAMPLE_REGISTERS};
+use crate::error::{finish, Error, Result};
+use crate::solution::Solution;
 
 use nom::bytes::complete::tag;
 use nom::character::complete::space0;
+use nom::combinator::map_opt;
 use nom::multi::{many1, separated_list};
 use nom::IResult;
 
-pub fn run() {
-    let input = fs::read_to_string("day16.txt").unwrap();
+pub struct Day16;
 
-    println!("16:1 - {}", run_1(&input));
-    println!("16:2 - {}", run_2(&input));
-}
+impl Solution for Day16 {
+    type Input = Program;
+    type Part1 = usize;
+    type Part2 = usize;
 
-fn run_1(input: &str) -> usize {
-    let (_, samples) = parse_samples(input).unwrap();
+    fn parse(input: &str) -> Result<Self::Input> {
+        let (code, samples) = finish(input, parse_samples(input))?;
+        // The program follows the samples, keep its line numbers relative to the whole input
+        let offset = input[..input.len() - code.len()].lines().count();
+        Ok(Program {
+            samples,
+            code: code.to_owned(),
+            offset,
+        })
+    }
 
-    let ops = [
-        addr, addi, mulr, muli, banr, bani, borr, bori, setr, seti, gtir, gtri, gtrr, eqir, eqri,
-        eqrr,
-    ];
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(&input.samples))
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        run_2(input)
+    }
+}
 
+fn run_1(samples: &[Sample]) -> usize {
     let mut cnt = 0;
     for sample in samples {
-        let mut sample_cnt = 0;
-        for op in &ops {
-            let mut regs = sample.before.clone();
-            op(
-                &mut regs,
-                sample.instruction.a,
-                sample.instruction.b,
-                sample.instruction.c,
-            );
-
-            if regs == sample.after {
-                sample_cnt += 1;
-            }
-        }
+        let sample_cnt = Opcode::ALL
+            .iter()
+            .filter(|op| is_match(**op, sample))
+            .count();
         if sample_cnt >= 3 {
             cnt += 1;
         }
@@ -48,63 +55,338 @@ fn run_1(input: &str) -> usize {
     cnt
 }
 
-type OP = fn(&mut Registers, usize, usize, usize);
+// Candidate operations for an opcode, bit n stands for Opcode::ALL[n]
+type Candidates = u16;
+
+fn bit(op: Opcode) -> Candidates {
+    1 << op as usize
+}
+
+fn ops(candidates: Candidates) -> Vec<Opcode> {
+    Opcode::ALL
+        .iter()
+        .filter(|op| candidates & bit(**op) != 0)
+        .cloned()
+        .collect()
+}
 
-fn run_2(input: &str) -> usize {
-    let (_, program) = parse_program(input).unwrap();
+// Few samples can fit a huge number of mappings, only this many are collected
+pub const MAX_MAPPINGS: usize = 100;
 
-    let ops = [
-        addr, addi, mulr, muli, banr, bani, borr, bori, setr, seti, gtir, gtri, gtrr, eqir, eqri,
-        eqrr,
-    ];
+// An operation that does not match a sample of an opcode, only the first such sample is kept
+#[derive(Debug, Clone, PartialEq)]
+pub struct Elimination {
+    pub opcode: usize,
+    pub op: Opcode,
+    // The line of the sample in the input
+    pub line: usize,
+}
 
-    let mut op_lookup: HashMap<usize, Vec<usize>> = HashMap::new();
+// What the samples tell about the opcodes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inference {
+    // The operations that match every sample of an opcode
+    pub candidates: BTreeMap<usize, Vec<Opcode>>,
+    // Sorted by opcode and operation
+    pub eliminations: Vec<Elimination>,
+    // The mappings of opcodes to distinct operations that fit all samples, at most
+    // MAX_MAPPINGS of them
+    pub mappings: Vec<Mapping>,
+}
 
-    for sample in program.samples {
-        if !op_lookup.contains_key(&sample.instruction.opcode) {
-            op_lookup.insert(sample.instruction.opcode, (0..ops.len()).collect());
+// Removes the only candidate of an opcode from all others, until nothing changes. Returns
+// false if an opcode is left without candidates.
+fn propagate(domains: &mut [(usize, Candidates)]) -> bool {
+    loop {
+        let mut changed = false;
+        for n in 0..domains.len() {
+            let single = domains[n].1;
+            if single == 0 {
+                return false;
+            }
+            if single.count_ones() > 1 {
+                continue;
+            }
+            for (m, (_, c)) in domains.iter_mut().enumerate() {
+                if m != n && *c & single != 0 {
+                    *c &= !single;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return true;
         }
+    }
+}
 
-        op_lookup
-            .get_mut(&sample.instruction.opcode)
-            .unwrap()
-            .retain(|op| is_match(&ops[*op], &sample));
+// Propagates, then tries each candidate of the opcode with the fewest
+fn search(mut domains: Vec<(usize, Candidates)>, mappings: &mut Vec<Mapping>) {
+    if mappings.len() >= MAX_MAPPINGS || !propagate(&mut domains) {
+        return;
+    }
+    let open = domains
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, c))| c.count_ones() > 1)
+        .min_by_key(|(_, (_, c))| c.count_ones());
+    match open {
+        None => mappings.push(
+            domains
+                .iter()
+                .map(|(opcode, c)| (*opcode, Opcode::ALL[c.trailing_zeros() as usize]))
+                .collect(),
+        ),
+        Some((n, &(_, c))) => {
+            for op in ops(c) {
+                let mut guess = domains.clone();
+                guess[n].1 = bit(op);
+                search(guess, mappings);
+            }
+        }
     }
+}
 
-    let mut code_to_op: HashMap<usize, usize> = HashMap::new();
-    while !op_lookup.is_empty() {
-        let mut singulars: Vec<usize> = Vec::new();
-        op_lookup.retain(|k, v| {
-            if v.len() == 1 {
-                singulars.push(v[0]);
-                code_to_op.insert(*k, v[0]);
+fn infer(samples: &[Sample]) -> Inference {
+    let mut domains: BTreeMap<usize, Candidates> = BTreeMap::new();
+    let mut eliminations = Vec::new();
+    for (n, sample) in samples.iter().enumerate() {
+        let opcode = sample.instruction.opcode;
+        let candidates = domains.entry(opcode).or_insert(Candidates::MAX);
+        for op in Opcode::ALL.iter() {
+            if *candidates & bit(*op) != 0 && !is_match(*op, sample) {
+                *candidates &= !bit(*op);
+                // Every sample takes four lines
+                eliminations.push(Elimination {
+                    opcode,
+                    op: *op,
+                    line: 4 * n + 1,
+                });
             }
-            v.len() != 1
-        });
+        }
+    }
+    eliminations.sort_by_key(|e| (e.opcode, e.op));
 
-        for (_, v) in op_lookup.iter_mut() {
-            v.retain(|op| !singulars.contains(op));
+    let mut mappings = Vec::new();
+    search(
+        domains.iter().map(|(o, c)| (*o, *c)).collect(),
+        &mut mappings,
+    );
+    Inference {
+        candidates: domains.iter().map(|(o, c)| (*o, ops(*c))).collect(),
+        eliminations,
+        mappings,
+    }
+}
+
+impl Inference {
+    // The mapping if it is the only one that fits
+    pub fn mapping(&self) -> Result<&Mapping> {
+        match self.mappings.as_slice() {
+            [mapping] => Ok(mapping),
+            [] => {
+                let impossible: Vec<usize> = self
+                    .candidates
+                    .iter()
+                    .filter(|(_, ops)| ops.is_empty())
+                    .map(|(opcode, _)| *opcode)
+                    .collect();
+                if impossible.is_empty() {
+                    Err(Error::new(
+                        "the samples do not fit any mapping of opcodes to distinct operations",
+                    ))
+                } else {
+                    Err(Error::new(format!(
+                        "no operation matches every sample of opcodes {:?}",
+                        impossible
+                    )))
+                }
+            }
+            mappings => {
+                let ambiguous: Vec<usize> = self
+                    .candidates
+                    .keys()
+                    .filter(|opcode| mappings.iter().any(|m| m[opcode] != mappings[0][opcode]))
+                    .cloned()
+                    .collect();
+                let at_least = if mappings.len() == MAX_MAPPINGS {
+                    "at least "
+                } else {
+                    ""
+                };
+                Err(Error::new(format!(
+                    "the samples fit {}{} mappings, opcodes {:?} are ambiguous",
+                    at_least,
+                    mappings.len(),
+                    ambiguous
+                )))
+            }
         }
     }
 
-    let mut regs = vec![0, 0, 0, 0];
+    // The operations left for each opcode and the samples that ruled out the others
+    pub fn explain(&self) -> String {
+        let mut s = String::new();
+        for (opcode, ops) in self.candidates.iter() {
+            let ops: Vec<&str> = ops.iter().map(|op| op.mnemonic()).collect();
+            if ops.is_empty() {
+                s += &format!("opcode {}: no operation\n", opcode);
+            } else {
+                s += &format!("opcode {}: {}\n", opcode, ops.join(" "));
+            }
+            for e in self.eliminations.iter().filter(|e| e.opcode == *opcode) {
+                s += &format!("    {} ruled out by the sample at line {}\n", e.op, e.line);
+            }
+        }
+        s
+    }
+}
 
-    for i in program.instructions {
-        ops[code_to_op[&i.opcode]](&mut regs, i.a, i.b, i.c);
+// The operations a single sample matches
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleMatches {
+    // The line of the sample in the input
+    pub line: usize,
+    pub opcode: usize,
+    pub ops: Vec<Opcode>,
+}
+
+// How each sample behaves under every operation, grouped by opcode
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    // The samples of each opcode in input order
+    pub samples: BTreeMap<usize, Vec<SampleMatches>>,
+}
+
+fn analyse_samples(samples: &[Sample]) -> Analysis {
+    let mut analysis = Analysis {
+        samples: BTreeMap::new(),
+    };
+    for (n, sample) in samples.iter().enumerate() {
+        let opcode = sample.instruction.opcode;
+        let ops = Opcode::ALL
+            .iter()
+            .filter(|op| is_match(**op, sample))
+            .cloned()
+            .collect();
+        analysis
+            .samples
+            .entry(opcode)
+            .or_default()
+            .push(SampleMatches {
+                line: 4 * n + 1,
+                opcode,
+                ops,
+            });
+    }
+    analysis
+}
+
+impl Analysis {
+    // Samples no operation explains, the input is corrupt if there are any
+    pub fn unmatched(&self) -> Vec<&SampleMatches> {
+        let mut unmatched: Vec<&SampleMatches> = self
+            .samples
+            .values()
+            .flatten()
+            .filter(|s| s.ops.is_empty())
+            .collect();
+        unmatched.sort_by_key(|s| s.line);
+        unmatched
+    }
+
+    // A matrix per opcode with a row per sample and a column per operation, `x` where the
+    // sample rules the operation out. The last row counts the samples that rule each one out.
+    pub fn report(&self) -> String {
+        let mut s = String::new();
+        // Writing to a String cannot fail
+        self.write_report(&mut s).unwrap();
+        s
+    }
+
+    fn write_report(&self, s: &mut String) -> fmt::Result {
+        let header: Vec<String> = Opcode::ALL
+            .iter()
+            .map(|op| format!(" {}", op.mnemonic()))
+            .collect();
+        for (opcode, samples) in self.samples.iter() {
+            writeln!(s, "opcode {}: {} samples", opcode, samples.len())?;
+            writeln!(s, "{:>8}{}", "line", header.concat())?;
+            for sample in samples {
+                let row: Vec<String> = Opcode::ALL
+                    .iter()
+                    .map(|op| {
+                        let mark = if sample.ops.contains(op) { "." } else { "x" };
+                        format!("{:>5}", mark)
+                    })
+                    .collect();
+                writeln!(s, "{:>8}{}", sample.line, row.concat())?;
+            }
+            let totals: Vec<String> = Opcode::ALL
+                .iter()
+                .map(|op| {
+                    let n = samples.iter().filter(|s| !s.ops.contains(op)).count();
+                    format!("{:>5}", n)
+                })
+                .collect();
+            writeln!(s, "{:>8}{}", "ruled", totals.concat())?;
+        }
+        let unmatched = self.unmatched();
+        if unmatched.is_empty() {
+            writeln!(s, "every sample matches an operation")
+        } else {
+            writeln!(
+                s,
+                "{} samples match no operation, the input may be corrupt:",
+                unmatched.len()
+            )?;
+            for sample in unmatched {
+                writeln!(s, "    line {}, opcode {}", sample.line, sample.opcode)?;
+            }
+            Ok(())
+        }
     }
+}
+
+// How the samples in a day 16 input behave, see Analysis::report
+pub fn analyse(program: &Program) -> Analysis {
+    analyse_samples(&program.samples)
+}
+
+// Every opcode that appears in a sample, with the only operation that fits all samples
+fn opcode_mapping(samples: &[Sample]) -> Result<Mapping> {
+    infer(samples).mapping().cloned()
+}
 
-    regs[0]
+// What the samples in a day 16 input tell about the opcodes
+pub fn inference(program: &Program) -> Inference {
+    infer(&program.samples)
 }
 
-fn is_match(op: &OP, sample: &Sample) -> bool {
+// The opcode mapping from the samples in a day 16 input, for use with asm::translate
+pub fn mapping(program: &Program) -> Result<Mapping> {
+    opcode_mapping(&program.samples)
+}
+
+fn run_2(program: &Program) -> Result<usize> {
+    let mapping = opcode_mapping(&program.samples)?;
+    let listing = asm::translate(&program.code, &mapping).map_err(|e| Error {
+        line: e.line.map(|l| l + program.offset),
+        ..e
+    })?;
+
+    let mut vm = listing.vm()?;
+    vm.run_compiled()?;
+    Ok(vm.regs[0])
+}
+
+// An operation that faults does not match
+fn is_match(op: Opcode, sample: &Sample) -> bool {
     let mut r = sample.before.clone();
-    op(
-        &mut r,
-        sample.instruction.a,
-        sample.instruction.b,
-        sample.instruction.c,
-    );
-    r == sample.after
+    let i = &sample.instruction;
+    op.execute_checked(&mut r, i.a, i.b, i.c, Overflow::Error)
+        .is_ok()
+        && r == sample.after
 }
 
 fn space_usize_val(i: &str) -> IResult<&str, usize> {
@@ -115,9 +397,13 @@ fn space_usize_val(i: &str) -> IResult<&str, usize> {
 
 fn parse_regs(i: &str) -> IResult<&str, Registers> {
     let (i, _) = tag("[")(i)?;
-    let (i, vals) = separated_list(tag(","), space_usize_val)(i)?;
+    let (i, regs) = map_opt(separated_list(tag(","), space_usize_val), |v| {
+        Some(v)
+            .filter(|v| v.len() == SAMPLE_REGISTERS)
+            .and_then(|v| Registers::from_slice(&v))
+    })(i)?;
     let (i, _) = tag("]")(i)?;
-    Ok((i, vals))
+    Ok((i, regs))
 }
 
 // Before: [3, 2, 1, 1]
@@ -140,32 +426,28 @@ fn parse_after(i: &str) -> IResult<&str, Registers> {
     Ok((i, regs))
 }
 
+// An instruction with its numeric opcode, before the opcodes are known
 #[derive(Debug, PartialEq)]
-struct Instruction {
+struct RawInstruction {
     opcode: usize,
     a: usize,
     b: usize,
     c: usize,
 }
 
-fn parse_instruction(i: &str) -> IResult<&str, Instruction> {
-    let (i, vals) = separated_list(tag(" "), usize_val)(i)?;
+fn parse_instruction(i: &str) -> IResult<&str, RawInstruction> {
+    let (i, opcode) = usize_val(i)?;
+    let (i, a) = space_usize_val(i)?;
+    let (i, b) = space_usize_val(i)?;
+    let (i, c) = space_usize_val(i)?;
     let (i, _) = tag("\n")(i)?;
-    Ok((
-        i,
-        Instruction {
-            opcode: vals[0],
-            a: vals[1],
-            b: vals[2],
-            c: vals[3],
-        },
-    ))
+    Ok((i, RawInstruction { opcode, a, b, c }))
 }
 
 #[derive(Debug)]
 struct Sample {
     before: Registers,
-    instruction: Instruction,
+    instruction: RawInstruction,
     after: Registers,
 }
 
@@ -188,22 +470,14 @@ fn parse_samples(i: &str) -> IResult<&str, Vec<Sample>> {
     many1(parse_sample)(i)
 }
 
-struct Program {
+// The samples and the program that follows them, the program is only translated once the
+// opcodes are known
+#[derive(Debug)]
+pub struct Program {
     samples: Vec<Sample>,
-    instructions: Vec<Instruction>,
-}
-
-fn parse_program(i: &str) -> IResult<&str, Program> {
-    let (i, samples) = parse_samples(i)?;
-    let (i, _) = tag("\n\n")(i)?;
-    let (i, instructions) = many1(parse_instruction)(i)?;
-    Ok((
-        i,
-        Program {
-            samples,
-            instructions,
-        },
-    ))
+    code: String,
+    // Lines before the program
+    offset: usize,
 }
 
 #[cfg(test)]
@@ -228,7 +502,7 @@ mod tests {
         let (_, insts) = parse_instruction("1 2 3 4\n").unwrap();
         assert_eq!(
             insts,
-            Instruction {
+            RawInstruction {
                 opcode: 1,
                 a: 2,
                 b: 3,
@@ -242,9 +516,116 @@ mod tests {
 
     #[test]
     fn aoc16_run_1() {
+        let (_, samples) =
+            parse_samples("Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]\n\n").unwrap();
+        assert_eq!(run_1(&samples), 1);
+        let e = Day16::parse("Before: [3, 2, 1, 1]\n9 2 1\nAfter:  [3, 2, 2, 1]\n\n").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(2), Some(6)));
+    }
+
+    // A sample that only seti matches
+    const SETI: &str = "Before: [0, 0, 0, 0]\n{} 3 0 0\nAfter:  [3, 0, 0, 0]\n\n";
+
+    #[test]
+    fn aoc16_inference() {
+        // Matches addi, mulr and seti
+        let input = "Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]\n\n";
+        let inference = inference(&Day16::parse(input).unwrap());
+        assert_eq!(inference.mappings.len(), 3);
         assert_eq!(
-            run_1("Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]\n\n"),
-            1
+            inference.mapping().unwrap_err().message,
+            "the samples fit 3 mappings, opcodes [9] are ambiguous"
+        );
+        let explanation = inference.explain();
+        assert!(explanation.starts_with(
+            "opcode 9: addi mulr seti\n    addr ruled out by the sample at line 1\n    \
+             muli ruled out by the sample at line 1\n"
+        ));
+        assert_eq!(explanation.lines().count(), 14);
+
+        // With seti taken by another opcode the choice is between two
+        let input = SETI.replace("{}", "4") + input;
+        let inference = super::inference(&Day16::parse(&input).unwrap());
+        assert_eq!(inference.mappings.len(), 2);
+        assert_eq!(
+            inference.eliminations[15],
+            Elimination {
+                opcode: 9,
+                op: Opcode::Addr,
+                line: 5
+            }
         );
+
+        let input = SETI.replace("{}", "1") + &SETI.replace("{}", "2");
+        assert_eq!(
+            super::inference(&Day16::parse(&input).unwrap())
+                .mapping()
+                .unwrap_err()
+                .message,
+            "the samples do not fit any mapping of opcodes to distinct operations"
+        );
+        let input = "Before: [0, 0, 0, 0]\n5 0 0 0\nAfter:  [9, 9, 9, 9]\n\n";
+        let inference = super::inference(&Day16::parse(input).unwrap());
+        assert_eq!(
+            inference.mapping().unwrap_err().message,
+            "no operation matches every sample of opcodes [5]"
+        );
+        assert!(inference.explain().starts_with("opcode 5: no operation\n"));
+
+        let input = std::fs::read_to_string("inputs/day16.txt").unwrap();
+        let inference = super::inference(&Day16::parse(&input).unwrap());
+        assert_eq!(inference.mappings.len(), 1);
+        assert_eq!(inference.mapping().unwrap().len(), 16);
+    }
+
+    #[test]
+    fn aoc16_analyse() {
+        let input = SETI.replace("{}", "4")
+            + "Before: [3, 2, 1, 1]\n9 2 1 2\nAfter:  [3, 2, 2, 1]\n\n"
+            + &SETI.replace("{}", "4")
+            + "Before: [0, 0, 0, 0]\n5 0 0 0\nAfter:  [9, 9, 9, 9]\n\n";
+        let analysis = analyse(&Day16::parse(&input).unwrap());
+        let counts: Vec<(usize, usize)> = analysis
+            .samples
+            .iter()
+            .map(|(o, s)| (*o, s.len()))
+            .collect();
+        assert_eq!(counts, [(4, 2), (5, 1), (9, 1)]);
+        assert_eq!(
+            analysis.samples[&9][0].ops,
+            [Opcode::Addi, Opcode::Mulr, Opcode::Seti]
+        );
+        assert_eq!(analysis.samples[&4][1].line, 9);
+        let unmatched = analysis.unmatched();
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!((unmatched[0].line, unmatched[0].opcode), (13, 5));
+
+        let report = analysis.report();
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "opcode 4: 2 samples");
+        assert!(lines[1].starts_with("    line addr addi"));
+        assert_eq!(
+            lines[2],
+            "       1    x    x    x    x    x    x    x    x    x    .    x    x    x    x    x    x"
+        );
+        assert_eq!(
+            lines[4],
+            "   ruled    2    2    2    2    2    2    2    2    2    0    2    2    2    2    2    2"
+        );
+        assert_eq!(
+            &lines[lines.len() - 2..],
+            [
+                "1 samples match no operation, the input may be corrupt:",
+                "    line 13, opcode 5"
+            ]
+        );
+
+        let input = std::fs::read_to_string("inputs/day16.txt").unwrap();
+        let analysis = analyse(&Day16::parse(&input).unwrap());
+        assert_eq!(analysis.samples.len(), 16);
+        assert!(analysis.unmatched().is_empty());
+        assert!(analysis
+            .report()
+            .ends_with("every sample matches an operation\n"));
     }
 }
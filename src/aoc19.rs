@@ -1,106 +1,49 @@
-use nom::bytes::complete::tag;
-use nom::character::complete::{alphanumeric1, space1};
-use nom::multi::{many1, separated_list};
-use nom::sequence::pair;
-use nom::*;
-use std::fs;
+use crate::asm::{self, Listing};
+use crate::error::{Error, Result};
+use crate::opcodes::{Outcome, RunOptions};
+use crate::solution::Solution;
 
-use crate::helper::usize_val;
-use crate::opcodes;
+pub struct Day19;
 
-pub fn run() {
-    let input = fs::read_to_string("day19.txt").unwrap();
-    println!("19:1 - {}", run_1(&input, 0));
-    println!("19:2 - {}", run_2(&input, 1));
-}
-
-fn parse_ip(i: &str) -> IResult<&str, usize> {
-    let (i, _) = tag("#ip")(i)?;
-    let (i, _) = space1(i)?;
-    let (i, ip) = usize_val(i)?;
-    let (i, _) = tag("\n")(i)?;
-    Ok((i, ip))
-}
-
-fn parse_instruction(i: &str) -> IResult<&str, (String, Vec<usize>)> {
-    let (i, instruction) = alphanumeric1(i)?;
-    let (i, _) = space1(i)?;
-    let (i, vals) = separated_list(tag(" "), usize_val)(i)?;
-    let (i, _) = tag("\n")(i)?;
-    Ok((i, (instruction.to_string(), vals)))
-}
-
-fn parse(i: &str) -> IResult<&str, (usize, Vec<(String, Vec<usize>)>)> {
-    pair(parse_ip, many1(parse_instruction))(i)
-}
-
-fn run_1(input: &str, reg0_start: usize) -> usize {
-    let (_, (ip, instructions)) = parse(input).unwrap();
+impl Solution for Day19 {
+    type Input = Listing;
+    type Part1 = usize;
+    type Part2 = usize;
 
-    // Init ip register to 0
-    let mut regs = vec![0; 6];
-    regs[0] = reg0_start;
-    regs[ip] = 0;
+    fn parse(input: &str) -> Result<Self::Input> {
+        asm::parse(input)
+    }
 
-    let mut last_r0 = 0;
-    let mut fst = true;
-    while regs[ip] < instructions.len() {
-        println!("{} - {:?}", regs[ip], regs);
-        if regs[ip] > 11 {}
-        if regs[0] != last_r0 {
-            println!("{} - {:?}", regs[ip], regs);
-            last_r0 = regs[0];
-        }
-        if fst && regs[ip] == 2 {
-            fst = false;
-            // 3 => 4
-            // 4 =-> 7
-            // 5 => 6
-            // 6 => 12
-            // 8 => 15
-            // 10 => 18
-            // 20 => 42
-            // 40
-            // regs[3] = 4; 7
-            // regs[3] = 8; 15
-            // regs[3] = 16; 31;
-            // regs[3] = 31; 32;
-            // regs[3] = 32; 63;
-            // regs[3] = 33; 48
-            regs[3] = 33;
-            // regs[3] = 64; 127;
-            // regs[3] = 40; 90
-            // regs[3] = 41; 42
-            // regs[3] = 45; 78
-            // regs[3] = 100; 217
-            // regs[3] = 1000; 2340;
-            // regs[3] = 20000; 49203;
-            // regs[3] = 40000; 99187;
-        }
-        let (i, v) = &instructions[regs[ip]];
-        match i.as_ref() {
-            "addi" => opcodes::addi(&mut regs, v[0], v[1], v[2]),
-            "addr" => opcodes::addr(&mut regs, v[0], v[1], v[2]),
-            "eqrr" => opcodes::eqrr(&mut regs, v[0], v[1], v[2]),
-            "gtrr" => opcodes::gtrr(&mut regs, v[0], v[1], v[2]),
-            "muli" => opcodes::muli(&mut regs, v[0], v[1], v[2]),
-            "mulr" => opcodes::mulr(&mut regs, v[0], v[1], v[2]),
-            "seti" => opcodes::seti(&mut regs, v[0], v[1], v[2]),
-            "setr" => opcodes::setr(&mut regs, v[0], v[1], v[2]),
-            i => panic!("Unhandled instruction {}", i),
-        }
-        regs[ip] += 1;
+    fn part_one(program: &Self::Input) -> Result<Self::Part1> {
+        run(program, 0)
     }
 
-    // println!("regs: {:?}", regs);
-    regs[0]
+    fn part_two(program: &Self::Input) -> Result<Self::Part2> {
+        run(program, 1)
+    }
 }
 
-fn run_2(_input: &str, _reg0_start: usize) -> usize {
-    // let mut regs = vec![0, 10550400, 1, 10551374, 0, 0];
+// Far more than either part takes with the loops replaced, and a program that never halts is
+// usually caught repeating itself long before
+const RUN_OPTIONS: RunOptions = RunOptions {
+    max_steps: Some(100_000_000),
+    timeout: None,
+    detect_loops: true,
+};
 
-    // regs[0]
-    0
+// Starting with 1 in r0 makes the program sum the divisors of a much larger number, which only
+// finishes in time with the loops replaced by native code
+fn run(program: &Listing, reg0_start: usize) -> Result<usize> {
+    let mut vm = program.vm()?;
+    vm.optimise();
+    vm.regs[0] = reg0_start;
+    match vm.run_with(&RUN_OPTIONS)? {
+        Outcome::Halted { .. } => Ok(vm.regs[0]),
+        outcome => Err(Error::new(format!(
+            "the program did not halt, it {}",
+            outcome
+        ))),
+    }
 }
 
 /*
@@ -148,14 +91,30 @@ R0 = 1
 mod tests {
     use super::*;
     #[test]
-    fn aoc19_parse() {
-        assert_eq!(
-            parse_instruction("addi 2 16 2\n"),
-            Ok(("", ("addi".to_string(), vec![2, 16, 2])))
-        );
+    fn aoc19_run() {
+        let input = "#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5
+";
+        assert_eq!(run(&Day19::parse(input).unwrap(), 0), Ok(6));
+
+        // Jumps back to ip 1 forever
+        let forever = Day19::parse("#ip 1\nseti 3 0 2\nseti 0 0 1\n").unwrap();
         assert_eq!(
-            parse_instruction("seti 1 0 4\n"),
-            Ok(("", ("seti".to_string(), vec![1, 0, 4])))
+            run(&forever, 0).unwrap_err().message,
+            "the program did not halt, it loops forever at ip 1, the state repeats every 1 \
+             steps (found after 2 steps)"
         );
+
+        // Without the loops replaced by native code
+        let program = Day19::parse(&std::fs::read_to_string("inputs/day19.txt").unwrap()).unwrap();
+        let mut vm = program.vm().unwrap();
+        vm.run().unwrap();
+        assert_eq!(run(&program, 0), Ok(vm.regs[0]));
     }
 }
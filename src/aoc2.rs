@@ -0,0 +1,109 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error::{Error, Result};
+use crate::solution::Solution;
+
+pub struct Day2;
+
+impl Solution for Day2 {
+    type Input = Vec<String>;
+    type Part1 = usize;
+    type Part2 = String;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        Ok(input.lines().map(|l| l.trim().to_owned()).collect())
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(input))
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        run_2(input)
+    }
+}
+
+// (has a letter exactly twice, has a letter exactly three times)
+fn letter_counts(id: &str) -> (bool, bool) {
+    let mut counts = HashMap::new();
+    for c in id.chars() {
+        *counts.entry(c).or_insert(0) += 1;
+    }
+    (
+        counts.values().any(|n| *n == 2),
+        counts.values().any(|n| *n == 3),
+    )
+}
+
+fn run_1(ids: &[String]) -> usize {
+    let counts: Vec<(bool, bool)> = ids.iter().map(|id| letter_counts(id)).collect();
+    let twos = counts.iter().filter(|c| c.0).count();
+    let threes = counts.iter().filter(|c| c.1).count();
+    twos * threes
+}
+
+// Two ids that differ in exactly one position are equal once that position is removed, so
+// instead of comparing all pairs every id is inserted once per position with the letter removed
+fn run_2(ids: &[String]) -> Result<String> {
+    // Identical ids do not differ in any position
+    let mut unique: Vec<&String> = ids.iter().collect();
+    unique.sort();
+    unique.dedup();
+
+    let max_len = unique
+        .iter()
+        .map(|id| id.chars().count())
+        .max()
+        .unwrap_or(0);
+    for pos in 0..max_len {
+        let mut seen = HashSet::new();
+        for id in unique.iter() {
+            let common: String = id
+                .chars()
+                .enumerate()
+                .filter(|(i, _)| *i != pos)
+                .map(|(_, c)| c)
+                .collect();
+            // Shorter ids are not changed by removing the position
+            if common.len() < id.len() && !seen.insert(common.clone()) {
+                return Ok(common);
+            }
+        }
+    }
+    Err(Error::new("no two box ids differ by exactly one letter"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(input: &str) -> Vec<String> {
+        Day2::parse(input).unwrap()
+    }
+
+    #[test]
+    fn aoc2_run_1() {
+        assert_eq!(letter_counts("abcdef"), (false, false));
+        assert_eq!(letter_counts("bababc"), (true, true));
+        assert_eq!(letter_counts("abbcde"), (true, false));
+        assert_eq!(letter_counts("abcccd"), (false, true));
+        assert_eq!(letter_counts("aabcdd"), (true, false));
+        assert_eq!(letter_counts("ababab"), (false, true));
+        assert_eq!(
+            run_1(&ids(
+                "abcdef\nbababc\nabbcde\nabcccd\naabcdd\nabcdee\nababab\n"
+            )),
+            12
+        );
+    }
+
+    #[test]
+    fn aoc2_run_2() {
+        assert_eq!(
+            run_2(&ids("abcde\nfghij\nklmno\npqrst\nfguij\naxcye\nwvxyz\n")),
+            Ok("fgij".to_owned())
+        );
+        assert!(run_2(&ids("abcde\nabcde\nfghij")).is_err());
+        assert!(run_2(&ids("abcde\naxcye")).is_err());
+    }
+}
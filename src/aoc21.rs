@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use crate::asm::{self, Listing};
+use crate::error::{Error, Result};
+use crate::opcodes::Opcode;
+use crate::solution::Solution;
+
+pub struct Day21;
+
+impl Solution for Day21 {
+    type Input = Listing;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        asm::parse(input)
+    }
+
+    fn part_one(program: &Self::Input) -> Result<Self::Part1> {
+        Ok(watch(program, Some(1))?.values[0])
+    }
+
+    fn part_two(program: &Self::Input) -> Result<Self::Part2> {
+        Ok(watch(program, None)?.last_new())
+    }
+}
+
+// The program halts when r0 equals a value it computes over and over. The more values it
+// computes before the one in r0, the more instructions it executes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    // Every value r0 was compared to, in order
+    pub values: Vec<usize>,
+    // The index of the first comparison that saw the same registers as an earlier one, from
+    // there on the values repeat
+    pub repeats_at: Option<usize>,
+}
+
+impl Watch {
+    // The value that is compared to last before the values start to repeat
+    pub fn last_new(&self) -> usize {
+        let mut seen = HashSet::new();
+        let mut last = self.values[0];
+        for v in self.values.iter() {
+            if seen.insert(v) {
+                last = *v;
+            }
+        }
+        last
+    }
+}
+
+// No single value should take longer than this to compute
+const MAX_STEPS: usize = 100_000_000;
+
+// The ip of the only instruction reading r0, which compares it to the returned register.
+// Nothing may write r0, then only its starting value decides when the program halts.
+fn comparison(program: &Listing) -> Result<(usize, usize)> {
+    let instructions = program.instructions();
+    if program.ip_reg == Some(0) || instructions.iter().any(|i| i.c == 0) {
+        return Err(Error::new("register 0 is written by the program"));
+    }
+    let readers: Vec<usize> = (0..instructions.len())
+        .filter(|ip| instructions[*ip].reads().any(|r| r == 0))
+        .collect();
+    let ip = match readers.as_slice() {
+        [ip] => *ip,
+        _ => {
+            return Err(Error::new(format!(
+                "register 0 is read by {} instructions instead of one",
+                readers.len()
+            )))
+        }
+    };
+    let i = instructions[ip];
+    match (i.op, i.a, i.b) {
+        (Opcode::Eqrr, 0, r) | (Opcode::Eqrr, r, 0) if r != 0 => Ok((ip, r)),
+        _ => Err(Error::new(format!(
+            "register 0 is read by '{}' at ip {} instead of being compared",
+            i, ip
+        ))),
+    }
+}
+
+// Runs the program with r0 never matching, collecting at most `max_values` compared values
+// and otherwise until the registers repeat at the comparison
+pub fn watch(program: &Listing, max_values: Option<usize>) -> Result<Watch> {
+    let (ip, reg) = comparison(program)?;
+    let mut vm = program.vm()?;
+    vm.optimise();
+
+    let mut watch = Watch {
+        values: Vec::new(),
+        repeats_at: None,
+    };
+    let mut seen = HashSet::new();
+    let mut steps = 0;
+    while max_values.is_none_or(|max| watch.values.len() < max) {
+        if vm.ip == ip {
+            let value = vm.regs[reg];
+            vm.regs[0] = 0;
+            if !seen.insert(vm.regs.clone()) {
+                watch.repeats_at = Some(watch.values.len());
+                break;
+            }
+            watch.values.push(value);
+            vm.regs[0] = value.wrapping_add(1);
+            steps = 0;
+        }
+        if !vm.step()? {
+            return Err(Error::new(format!(
+                "the program halted after comparing r0 to {} values",
+                watch.values.len()
+            )));
+        }
+        steps += 1;
+        if steps > MAX_STEPS {
+            return Err(Error::new(format!(
+                "no comparison with r0 in {} steps",
+                MAX_STEPS
+            )));
+        }
+    }
+    Ok(watch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The shape of the day 21 inputs, which differ in the constant at ip 7
+    const PROGRAM: &str = "#ip 1
+seti 123 0 3
+bani 3 456 3
+eqri 3 72 3
+addr 3 1 1
+seti 0 0 1
+seti 0 4 3
+bori 3 65536 4
+seti 4332021 4 3
+bani 4 255 5
+addr 3 5 3
+bani 3 16777215 3
+muli 3 65899 3
+bani 3 16777215 3
+gtir 256 4 5
+addr 5 1 1
+addi 1 1 1
+seti 27 3 1
+seti 0 3 5
+addi 5 1 2
+muli 2 256 2
+gtrr 2 4 2
+addr 2 1 1
+addi 1 1 1
+seti 25 3 1
+addi 5 1 5
+seti 17 1 1
+setr 5 6 4
+seti 7 8 1
+eqrr 3 0 5
+addr 5 1 1
+seti 5 6 1
+";
+
+    // What the program computes, written out
+    fn values(n: usize) -> Vec<usize> {
+        let mut values = Vec::new();
+        let mut r3 = 0;
+        while values.len() < n {
+            let mut r4 = r3 | 65536;
+            r3 = 4332021;
+            loop {
+                r3 = (((r3 + (r4 & 255)) & 16777215) * 65899) & 16777215;
+                if r4 < 256 {
+                    break;
+                }
+                r4 /= 256;
+            }
+            values.push(r3);
+        }
+        values
+    }
+
+    #[test]
+    fn aoc21_watch() {
+        let program = Day21::parse(PROGRAM).unwrap();
+        let watch = watch(&program, Some(5)).unwrap();
+        assert_eq!(watch.values, values(5));
+        assert_eq!(watch.repeats_at, None);
+        assert_eq!(Day21::part_one(&program).unwrap(), watch.values[0]);
+
+        let watch = super::watch(&program, None).unwrap();
+        let n = watch.repeats_at.unwrap();
+        assert_eq!(n, watch.values.len());
+        assert_eq!(watch.values, values(n));
+        assert_eq!(Day21::part_two(&program).unwrap(), watch.last_new());
+
+        // The last new value is the one that makes the program run longest
+        let mut vm = program.vm().unwrap();
+        vm.optimise();
+        vm.regs[0] = watch.last_new();
+        let steps = vm.run().unwrap();
+        vm = program.vm().unwrap();
+        vm.optimise();
+        vm.regs[0] = watch.values[n / 2];
+        assert!(vm.run().unwrap() < steps);
+    }
+
+    #[test]
+    fn aoc21_comparison() {
+        let program = Day21::parse(PROGRAM).unwrap();
+        assert_eq!(comparison(&program).unwrap(), (28, 3));
+
+        let e = watch(&Day21::parse("#ip 1\naddr 0 0 2\n").unwrap(), None).unwrap_err();
+        assert_eq!(
+            e.message,
+            "register 0 is read by 'addr 0 0 2' at ip 0 instead of being compared"
+        );
+        let e = watch(
+            &Day21::parse("#ip 1\neqrr 0 2 2\nseti 3 0 0\n").unwrap(),
+            None,
+        );
+        assert_eq!(
+            e.unwrap_err().message,
+            "register 0 is written by the program"
+        );
+        let e = watch(&Day21::parse("#ip 1\neqrr 0 2 2\n").unwrap(), None);
+        assert_eq!(
+            e.unwrap_err().message,
+            "the program halted after comparing r0 to 1 values"
+        );
+    }
+}
@@ -1,11 +1,48 @@
+use nom::bytes::complete::tag;
+use nom::character::complete::{newline, space0};
+use nom::IResult;
 use pathfinding::prelude::{absdiff, astar};
 
+use crate::error::{parse_all, Error, Result};
+use crate::helper::usize_val;
+use crate::solution::Solution;
+
 type Coord = (usize, usize);
 
-pub fn run() {
-    println!("22:1 {}", run_1(11991, (6, 797)));
-    println!("22:2 {}", run_2(11991, (6, 797)));
-    // 1051
+pub struct Day22;
+
+impl Solution for Day22 {
+    type Input = (usize, Coord);
+    type Part1 = u64;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_all(input, parse(input))
+    }
+
+    fn part_one(&(depth, target): &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(depth, target))
+    }
+
+    fn part_two(&(depth, target): &Self::Input) -> Result<Self::Part2> {
+        // 1051
+        run_2(depth, target)
+    }
+}
+
+// depth: 11991
+// target: 6,797
+fn parse(i: &str) -> IResult<&str, (usize, Coord)> {
+    let (i, _) = tag("depth:")(i)?;
+    let (i, _) = space0(i)?;
+    let (i, depth) = usize_val(i)?;
+    let (i, _) = newline(i)?;
+    let (i, _) = tag("target:")(i)?;
+    let (i, _) = space0(i)?;
+    let (i, x) = usize_val(i)?;
+    let (i, _) = tag(",")(i)?;
+    let (i, y) = usize_val(i)?;
+    Ok((i, (depth, (x, y))))
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -54,9 +91,7 @@ fn create_map(width: usize, height: usize) -> Map {
 }
 
 fn geologic_index(x: usize, y: usize, target: &Coord, map: &Map) -> u64 {
-    if x == 0 && y == 0 {
-        0
-    } else if x == target.0 && y == target.1 {
+    if (x == 0 && y == 0) || (x == target.0 && y == target.1) {
         0
     } else if x == 0 {
         y as u64 * 48271
@@ -79,7 +114,7 @@ fn expand_map(map: &mut Map, target: &Coord, depth: usize) {
     *map = create_map(new_width, new_height);
     for y in 0..new_height {
         for x in 0..new_width {
-            let gl = erosion_level(x, y, &target, &map, depth);
+            let gl = erosion_level(x, y, target, map, depth);
             map[y][x] = gl;
         }
     }
@@ -106,14 +141,13 @@ fn run_1(depth: usize, target: Coord) -> u64 {
 struct State {
     target: Coord,
     depth: usize,
-    tool: Tool,
     map: Map,
 }
 
 impl State {
     fn new(depth: usize, target: Coord) -> Self {
-        let width = 1 * target.0 + 1;
-        let height = 1 * target.1 + 1;
+        let width = target.0 + 1;
+        let height = target.1 + 1;
         let mut map = create_map(width, height);
 
         for y in 0..height {
@@ -125,7 +159,6 @@ impl State {
         State {
             target,
             depth,
-            tool: Tool::Torch,
             map,
         }
     }
@@ -145,7 +178,6 @@ impl State {
         let cur_widht = self.map[0].len();
         let cur_height = self.map.len();
         if (x + 1) >= cur_widht || (y + 1) > cur_height {
-            println!("Expanding map");
             expand_map(&mut self.map, &self.target, self.depth);
         }
 
@@ -177,7 +209,7 @@ impl State {
     }
 
     fn distance_to_target(n: &Node, target: &Coord) -> usize {
-        (absdiff(n.pos.0, target.0) + absdiff(n.pos.1, target.1))
+        absdiff(n.pos.0, target.0) + absdiff(n.pos.1, target.1)
     }
 
     fn solve(&mut self) -> Option<(Vec<Node>, usize)> {
@@ -205,15 +237,25 @@ struct Node {
     tool: Tool,
 }
 
-fn run_2(depth: usize, target: (usize, usize)) -> usize {
+fn run_2(depth: usize, target: (usize, usize)) -> Result<usize> {
     let mut state = State::new(depth, target);
-    let res = state.solve().unwrap();
-    res.1
+    let (_, time) = state
+        .solve()
+        .ok_or_else(|| Error::new("there is no path to the target"))?;
+    Ok(time)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[test]
+    fn aoc22_parse() {
+        assert_eq!(
+            parse("depth: 510\ntarget: 10,10\n"),
+            Ok(("\n", (510, (10, 10))))
+        );
+    }
+
     #[test]
     fn aoc22_geologic_index() {
         let map = create_map(11, 11);
@@ -242,6 +284,6 @@ mod tests {
 
     #[test]
     fn aoc22_test_2() {
-        assert_eq!(run_2(510, (10, 10)), 45);
+        assert_eq!(run_2(510, (10, 10)), Ok(45));
     }
 }
@@ -2,15 +2,34 @@ use super::helper::*;
 use nom::branch::alt;
 use nom::bytes::complete::{tag, take_until};
 use nom::character::complete::{newline, space0};
-use nom::combinator::{map, opt};
+use nom::combinator::{cut, map, opt, verify};
 use nom::multi::separated_nonempty_list;
 use nom::sequence::{preceded, terminated};
 use nom::IResult;
-use std::fs;
 
-pub fn run() {
-    let input = fs::read_to_string("day24.txt").unwrap();
-    println!("24:1 {}", run_1(&input));
+use std::cmp::Reverse;
+
+use crate::error::{parse_all, Error, Result};
+use crate::solution::Solution;
+
+pub struct Day24;
+
+impl Solution for Day24 {
+    type Input = Battle;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_all(input, parse_armies(input))
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        run_1(input)
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        run_2(input)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -91,8 +110,8 @@ impl Attribute {
     }
 }
 
-#[derive(Debug, PartialEq)]
-struct Group {
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
     army_name: String,
     units: usize,
     hit_points: usize,
@@ -129,7 +148,8 @@ impl Group {
         move |i| {
             let (i, units) = u32_val(i)?;
             let (i, _) = tag(" units each with ")(i)?;
-            let (i, hit_points) = u32_val(i)?;
+            // A group without hit points could never be damaged, the battle divides by them
+            let (i, hit_points) = cut(verify(u32_val, |&hp| hp > 0))(i)?;
             let (i, _) = tag(" hit points ")(i)?;
             let (i, attrs) = opt(Attribute::parse)(i)?;
             let (weakness, immune) =
@@ -147,10 +167,10 @@ impl Group {
                     army_name: army_name.clone(),
                     units: units as usize,
                     hit_points: hit_points as usize,
-                    weakness: weakness,
-                    immune: immune,
+                    weakness,
+                    immune,
                     attack: attack as usize,
-                    attack_type: attack_type,
+                    attack_type,
                     initiative: initiative as usize,
                 },
             ))
@@ -167,7 +187,10 @@ fn parse_army(i: &str) -> IResult<&str, Vec<Group>> {
     Ok((i, groups))
 }
 
-fn parse_armies(i: &str) -> IResult<&str, (Vec<Group>, Vec<Group>)> {
+// The immune system and the infection
+type Battle = (Vec<Group>, Vec<Group>);
+
+fn parse_armies(i: &str) -> IResult<&str, Battle> {
     let (i, immune) = parse_army(i)?;
     let (i, _) = newline(i)?;
     let (i, _) = newline(i)?;
@@ -175,59 +198,156 @@ fn parse_armies(i: &str) -> IResult<&str, (Vec<Group>, Vec<Group>)> {
     Ok((i, (immune, inf)))
 }
 
-fn attack(g: &mut [Group], a: usize, d: usize) {
-    println!("{} attacks {}", &g[a].army_name, &g[d].army_name);
-    let dmg_multiplier = if g[d].weakness.contains(&g[a].attack_type) {
-        2
-    } else if g[d].immune.contains(&g[a].attack_type) {
-        0
-    } else {
-        1
-    };
-
-    let dmg = g[a].attack * dmg_multiplier;
-
-    let total_dmg = (dmg * g[a].units) as isize;
-    let total_def = (g[d].units * g[d].hit_points) as isize;
-    let alive = isize::max(total_def - total_dmg, 0) as usize / g[d].hit_points;
-    println!("{} damage to {} hp, {} alive", total_dmg, total_def, alive);
-    g[d].units = alive;
+impl Group {
+    fn effective_power(&self) -> usize {
+        self.units * self.attack
+    }
+
+    fn damage_to(&self, defender: &Group) -> usize {
+        if defender.immune.contains(&self.attack_type) {
+            0
+        } else if defender.weakness.contains(&self.attack_type) {
+            2 * self.effective_power()
+        } else {
+            self.effective_power()
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Outcome {
+    Won { army: String, units: usize },
+    // No army can kill a single unit of the other
+    Stalemate,
+}
+
+// Groups are numbered from 1 within their army, in input order
+fn label(groups: &[Group], idx: usize) -> usize {
+    groups[..idx]
+        .iter()
+        .filter(|g| g.army_name == groups[idx].army_name)
+        .count()
+        + 1
 }
 
-fn run_1(input: &str) -> usize {
-    let (_, (mut a1, mut a2)) = parse_armies(input).unwrap();
-    a1.append(&mut a2);
+// Each round every group selects a target, in order of decreasing effective power and
+// initiative, and then all groups attack in order of decreasing initiative.
+fn fight(mut groups: Vec<Group>, mut trace: Option<&mut Vec<String>>) -> Outcome {
     loop {
-        a1.sort_by(|g1, g2| g1.initiative.cmp(&g2.initiative));
-        let mut to_remove = Vec::new();
-        for i in 0..a1.len() {
-            for j in (i + 1)..a1.len() {
-                if a1[i].army_name != a1[j].army_name {
-                    attack(&mut a1, i, j);
-
-                    let d = &a1[j];
-                    if d.units == 0 {
-                        to_remove.push(j);
-                    }
-                }
-            }
+        let alive: Vec<usize> = (0..groups.len()).filter(|i| groups[*i].units > 0).collect();
+        if alive
+            .iter()
+            .all(|i| groups[*i].army_name == groups[alive[0]].army_name)
+        {
+            return match alive.first() {
+                Some(i) => Outcome::Won {
+                    army: groups[*i].army_name.clone(),
+                    units: alive.iter().map(|i| groups[*i].units).sum(),
+                },
+                None => Outcome::Stalemate,
+            };
         }
 
-        to_remove.sort();
-        to_remove.reverse();
+        let mut selection_order = alive.clone();
+        selection_order
+            .sort_by_key(|i| Reverse((groups[*i].effective_power(), groups[*i].initiative)));
+
+        // Each group can be targeted by at most one attacker
+        let mut targets: Vec<Option<usize>> = vec![None; groups.len()];
+        for a in selection_order {
+            let attacker = &groups[a];
+            targets[a] = alive
+                .iter()
+                .filter(|d| groups[**d].army_name != attacker.army_name)
+                .filter(|d| !targets.contains(&Some(**d)))
+                .filter(|d| attacker.damage_to(&groups[**d]) > 0)
+                .max_by_key(|d| {
+                    let defender = &groups[**d];
+                    (
+                        attacker.damage_to(defender),
+                        defender.effective_power(),
+                        defender.initiative,
+                    )
+                })
+                .cloned();
+        }
 
-        for idx in to_remove {
-            a1.remove(idx);
+        let mut attack_order = alive;
+        attack_order.sort_by_key(|i| Reverse(groups[*i].initiative));
+
+        let mut total_killed = 0;
+        for a in attack_order {
+            // Groups killed earlier in the round do not attack
+            let d = match targets[a] {
+                Some(d) if groups[a].units > 0 => d,
+                _ => continue,
+            };
+            let damage = groups[a].damage_to(&groups[d]);
+            let killed = usize::min(damage / groups[d].hit_points, groups[d].units);
+            groups[d].units -= killed;
+            total_killed += killed;
+
+            if let Some(trace) = trace.as_mut() {
+                trace.push(format!(
+                    "{} group {} attacks defending group {}, killing {} units",
+                    groups[a].army_name,
+                    label(&groups, a),
+                    label(&groups, d),
+                    killed
+                ));
+            }
         }
 
-        let first_name = &a1[0].army_name;
-        if a1.iter().all(|g| &g.army_name == first_name) {
-            break;
+        if total_killed == 0 {
+            return Outcome::Stalemate;
         }
     }
-    a1.iter().map(|g| g.units).sum()
 }
 
+fn boosted(input: &Battle, boost: usize) -> Vec<Group> {
+    let (immune, infection) = input;
+    immune
+        .iter()
+        .map(|g| Group {
+            attack: g.attack + boost,
+            ..g.clone()
+        })
+        .chain(infection.iter().cloned())
+        .collect()
+}
+
+// The attacks of every round, with the immune system boosted by `boost`
+pub fn battle_log(input: &str, boost: usize) -> Result<Vec<String>> {
+    let battle = parse_all(input, parse_armies(input))?;
+    let mut log = Vec::new();
+    fight(boosted(&battle, boost), Some(&mut log));
+    Ok(log)
+}
+
+fn run_1(input: &Battle) -> Result<usize> {
+    match fight(boosted(input, 0), None) {
+        Outcome::Won { units, .. } => Ok(units),
+        Outcome::Stalemate => Err(Error::new("the battle ends in a stalemate")),
+    }
+}
+
+// A larger boost does not always mean a better outcome for the immune system, groups may end
+// up selecting other targets, so every boost is tried in turn. Once every immune system attack
+// kills its whole target a larger boost can not change anything.
+fn run_2(input: &Battle) -> Result<usize> {
+    let (immune, infection) = input;
+    let max_boost: usize = infection.iter().map(|g| g.units * g.hit_points).sum();
+    for boost in 1..=max_boost {
+        if let Outcome::Won { army, units } = fight(boosted(input, boost), None) {
+            if army == immune[0].army_name {
+                return Ok(units);
+            }
+        }
+    }
+    Err(Error::new("no boost lets the immune system win"))
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -325,18 +445,47 @@ Infection:
         assert_eq!(2, inf.len());
     }
 
-    #[test]
-    fn aoc24_1() {
-        let armies = "Immune System:
+    const ARMIES: &str = "Immune System:
 17 units each with 5390 hit points (weak to radiation, bludgeoning) with an attack that does 4507 fire damage at initiative 2
 989 units each with 1274 hit points (immune to fire; weak to bludgeoning, slashing) with an attack that does 25 slashing damage at initiative 3
 
 Infection:
 801 units each with 4706 hit points (weak to radiation) with an attack that does 116 bludgeoning damage at initiative 1
 4485 units each with 2961 hit points (immune to radiation; weak to fire, cold) with an attack that does 12 slashing damage at initiative 4";
-        assert_eq!(5216, run_1(armies));
+
+    #[test]
+    fn aoc24_1() {
+        let battle = Day24::parse(ARMIES).unwrap();
+        assert_eq!(Ok(5216), run_1(&battle));
+
+        let log = battle_log(ARMIES, 0).unwrap();
+        assert_eq!(
+            log[..4].to_vec(),
+            [
+                "Infection group 2 attacks defending group 2, killing 84 units",
+                "Immune System group 2 attacks defending group 1, killing 4 units",
+                "Immune System group 1 attacks defending group 2, killing 51 units",
+                "Infection group 1 attacks defending group 1, killing 17 units",
+            ]
+        );
     }
 
     #[test]
-    fn aoc24_2() {}
+    fn aoc24_parse_error() {
+        let e = Day24::parse(&ARMIES.replace("4706 hit points", "0 hit points")).unwrap_err();
+        assert_eq!((e.line, e.column), (Some(6), Some(21)));
+    }
+
+    #[test]
+    fn aoc24_2() {
+        let battle = Day24::parse(ARMIES).unwrap();
+        assert_eq!(
+            fight(boosted(&battle, 1570), None),
+            Outcome::Won {
+                army: "Immune System".to_owned(),
+                units: 51
+            }
+        );
+        assert_eq!(Ok(51), run_2(&battle));
+    }
 }
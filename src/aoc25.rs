@@ -2,21 +2,35 @@ use super::helper::i32_val;
 use nom::bytes::complete::tag;
 use nom::character::complete::newline;
 use nom::combinator::opt;
-use nom::multi::{many0, separated_list};
+use nom::multi::{many0, separated_nonempty_list};
 use nom::sequence::terminated;
 use nom::IResult;
-use std::fs;
-
-pub fn run() {
-    let input = fs::read_to_string("day25.txt").unwrap();
-    let (_, vecs) = parse(&input).unwrap();
-    // let vecs = vecs.iter().filter(|v| !v.is_empty()).collect();
-    // println!("25:1 {}", run_1(&vecs));
-    println!("{:?}", vecs);
+
+use crate::error::{parse_all, Result};
+use crate::solution::{Solution, Unsolved};
+
+pub struct Day25;
+
+impl Solution for Day25 {
+    type Input = Vec<Vec<i32>>;
+    type Part1 = usize;
+    type Part2 = Unsolved;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_all(input, parse(input))
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(input))
+    }
+
+    fn part_two(_input: &Self::Input) -> Result<Self::Part2> {
+        Ok(Unsolved)
+    }
 }
 
 fn parse_vector(i: &str) -> IResult<&str, Vec<i32>> {
-    terminated(separated_list(tag(","), i32_val), opt(newline))(i)
+    terminated(separated_nonempty_list(tag(","), i32_val), opt(newline))(i)
 }
 
 fn parse(i: &str) -> IResult<&str, Vec<Vec<i32>>> {
@@ -41,7 +55,7 @@ fn run_1(input: &[Vec<i32>]) -> usize {
     let mut current = Vec::new();
 
     // Add the first one to the current constellation
-    current.push(v.pop().unwrap());
+    current.extend(v.pop());
 
     while !v.is_empty() {
         loop {
@@ -58,9 +72,7 @@ fn run_1(input: &[Vec<i32>]) -> usize {
         }
         constellations.push(current);
         current = Vec::new();
-        if v.len() > 0 {
-            current.push(v.pop().unwrap());
-        }
+        current.extend(v.pop());
     }
 
     if !current.is_empty() {
@@ -1,17 +1,32 @@
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::HashMap;
-use std::fs;
 
-pub fn run() {
-    let input = fs::read_to_string("day3.txt").unwrap();
+use crate::error::{parse_lines, Error, Result};
+use crate::solution::Solution;
 
-    println!("3:1 - {}", run_1(&input));
-    println!("3:2 - {}", run_2(&input));
+pub struct Day3;
+
+impl Solution for Day3 {
+    type Input = Vec<Claim>;
+    type Part1 = u32;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_lines(input, parse)
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(input))
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        run_2(input)
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-struct Claim {
+pub struct Claim {
     id: u32,
     x: u32,
     y: u32,
@@ -22,33 +37,41 @@ struct Claim {
 impl Claim {
     fn new(id: u32, x: u32, y: u32, w: u32, h: u32) -> Claim {
         Claim {
-            id: id,
-            x: x,
-            y: y,
+            id,
+            x,
+            y,
             width: w,
             height: h,
         }
     }
 }
 
-fn s2i(s: &str) -> u32 {
-    u32::from_str_radix(s, 10).unwrap()
+fn s2i(s: &str) -> Result<u32> {
+    s.parse::<u32>()
+        .map_err(|e| Error::new(format!("{}: {}", s, e)))
 }
 
-fn parse(row: &str) -> Claim {
+fn parse(row: &str) -> Result<Claim> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r#"#(\d+)\s+@\s+(\d+),(\d+):\s+(\d+)x(\d+)"#).unwrap();
     }
 
-    let c = RE.captures(row).unwrap();
-    Claim::new(s2i(&c[1]), s2i(&c[2]), s2i(&c[3]), s2i(&c[4]), s2i(&c[5]))
+    let c = RE
+        .captures(row)
+        .ok_or_else(|| Error::new(format!("expected '#id @ x,y: wxh', found '{}'", row)))?;
+    Ok(Claim::new(
+        s2i(&c[1])?,
+        s2i(&c[2])?,
+        s2i(&c[3])?,
+        s2i(&c[4])?,
+        s2i(&c[5])?,
+    ))
 }
 
-fn run_1(input: &str) -> u32 {
-    let components: Vec<Claim> = input.lines().map(parse).collect();
+fn run_1(components: &[Claim]) -> u32 {
     let mut clms = HashMap::new();
 
-    for c in components {
+    for c in components.iter() {
         for x in c.x..(c.x + c.width) {
             for y in c.y..(c.y + c.height) {
                 let counter = clms.entry((x, y)).or_insert(0);
@@ -65,8 +88,7 @@ fn run_1(input: &str) -> u32 {
     cnt
 }
 
-fn run_2(input: &str) -> u32 {
-    let components: Vec<Claim> = input.lines().map(parse).collect();
+fn run_2(components: &[Claim]) -> Result<u32> {
     let mut clms = HashMap::new();
 
     for c in components.iter() {
@@ -92,10 +114,10 @@ fn run_2(input: &str) -> u32 {
             }
         }
         if !found {
-            return c.id;
+            return Ok(c.id);
         }
     }
-    0
+    Err(Error::new("all claims overlap"))
 }
 
 #[cfg(test)]
@@ -104,18 +126,21 @@ mod tests {
     #[test]
     fn aoc3_parse() {
         assert_eq!(
-            Claim::new(1, 662, 777, 18, 27),
+            Ok(Claim::new(1, 662, 777, 18, 27)),
             parse("#1 @ 662,777: 18x27")
         );
+        assert!(parse("#1 @ 662,777 18x27").is_err());
     }
 
+    const CLAIMS: &str = "#1 @ 1,3: 4x4\n#2 @ 3,1: 4x4\n#3 @ 5,5: 2x2";
+
     #[test]
     fn aoc3_run_1() {
-        assert_eq!(4, run_1("#1 @ 1,3: 4x4\n#2 @ 3,1: 4x4\n#3 @ 5,5: 2x2"));
+        assert_eq!(4, run_1(&Day3::parse(CLAIMS).unwrap()));
     }
 
     #[test]
     fn aoc3_run_2() {
-        assert_eq!(3, run_2("#1 @ 1,3: 4x4\n#2 @ 3,1: 4x4\n#3 @ 5,5: 2x2"));
+        assert_eq!(Ok(3), run_2(&Day3::parse(CLAIMS).unwrap()));
     }
 }
@@ -1,19 +1,19 @@
 use std::collections::HashMap;
-use std::fs;
 
 use nom::branch::alt;
 use nom::bytes::complete::tag;
 use nom::character::complete::space0;
 use nom::combinator::opt;
-use nom::multi::many1;
 use nom::*;
 use std::cmp::Ordering;
 
 use super::helper::u32_val;
+use crate::error::{parse_all, parse_lines, Error, Result};
+use crate::solution::Solution;
 use chrono::prelude::*;
 
-fn date_time(i: &str) -> IResult<&str, DateTime<Local>> {
-    let (i, _) = tag("[")(i)?;
+fn date_time(start: &str) -> IResult<&str, DateTime<Local>> {
+    let (i, _) = tag("[")(start)?;
     let (i, year) = u32_val(i)?;
     let (i, _) = tag("-")(i)?;
     let (i, month) = u32_val(i)?;
@@ -24,12 +24,12 @@ fn date_time(i: &str) -> IResult<&str, DateTime<Local>> {
     let (i, _) = tag(":")(i)?;
     let (i, minute) = u32_val(i)?;
     let (i, _) = tag("]")(i)?;
-    Ok((
-        i,
-        Local
-            .ymd(year as i32 + 500, month, day)
-            .and_hms(hour, minute, 0),
-    ))
+    let dt = Local
+        .ymd_opt(year as i32 + 500, month, day)
+        .single()
+        .and_then(|d| d.and_hms_opt(hour, minute, 0))
+        .ok_or(nom::Err::Error((start, error::ErrorKind::Verify)))?;
+    Ok((i, dt))
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -64,7 +64,7 @@ fn action(i: &str) -> IResult<&str, Action> {
 }
 
 #[derive(Debug, PartialEq, Eq)]
-struct Line {
+pub struct Line {
     dt: DateTime<Local>,
     action: Action,
 }
@@ -89,8 +89,8 @@ fn parse_line(i: &str) -> IResult<&str, Line> {
     Ok((i, Line { dt, action }))
 }
 
-fn parse(i: &str) -> IResult<&str, Vec<Line>> {
-    many1(parse_line)(i)
+fn parse(input: &str) -> Result<Vec<Line>> {
+    parse_lines(input, |l| parse_all(l, parse_line(l)))
 }
 
 /*
@@ -99,16 +99,36 @@ fn parse(i: &str) -> IResult<&str, Vec<Line>> {
 [1518-06-21 00:43] falls asleep
 */
 
-pub fn run() {
-    let input = fs::read_to_string("day4.txt").unwrap();
-    let (_, mut lines) = parse(&input).unwrap();
-    lines.sort();
+pub struct Day4;
 
-    println!("4:1 {}", run_1(&lines));
-    println!("4:2 {}", run_2(&lines));
+impl Solution for Day4 {
+    type Input = Vec<Line>;
+    type Part1 = u32;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        let mut lines = parse(input)?;
+        lines.sort();
+        Ok(lines)
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        run_1(input)
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        run_2(input)
+    }
+}
+
+fn sleep_time(line: &Line, sleep_start: u32) -> Result<u32> {
+    line.dt
+        .minute()
+        .checked_sub(sleep_start)
+        .ok_or_else(|| Error::new(format!("wakes up before falling asleep at {}", line.dt)))
 }
 
-fn run_1(lines: &[Line]) -> u32 {
+fn run_1(lines: &[Line]) -> Result<u32> {
     let mut current_guard = 0;
     let mut sleep_start = 0;
     let mut acc_sleep_time = HashMap::new();
@@ -120,12 +140,9 @@ fn run_1(lines: &[Line]) -> u32 {
             Action::WakesUp => {
                 let wakeup_time = line.dt.minute();
                 let old = acc_sleep_time.entry(current_guard).or_insert(0);
-                *old += wakeup_time - sleep_start;
+                *old += sleep_time(line, sleep_start)?;
 
-                if !sleep_minute_count.contains_key(&current_guard) {
-                    sleep_minute_count.insert(current_guard, HashMap::new());
-                }
-                let min_lu = sleep_minute_count.get_mut(&current_guard).unwrap();
+                let min_lu = sleep_minute_count.entry(current_guard).or_default();
                 for minute in sleep_start..wakeup_time {
                     let min_cnt = min_lu.entry(minute).or_insert(0);
                     *min_cnt += 1;
@@ -134,23 +151,22 @@ fn run_1(lines: &[Line]) -> u32 {
         }
     }
 
+    let no_sleep = || Error::new("no guard ever falls asleep");
     let (sleepiest_guard, _) = acc_sleep_time
         .iter()
         .max_by(|(_, a), (_, b)| a.cmp(b))
-        .unwrap();
+        .ok_or_else(no_sleep)?;
     let (sleepiest_minute, _) = sleep_minute_count
-        .get(&sleepiest_guard)
-        .unwrap()
-        .iter()
-        .max_by(|(_, a), (_, b)| a.cmp(b))
-        .unwrap();
+        .get(sleepiest_guard)
+        .and_then(|m| m.iter().max_by(|(_, a), (_, b)| a.cmp(b)))
+        .ok_or_else(no_sleep)?;
     // println!("Sleepiest: {:?}", sleepiest_guard);
     // println!("Sleepiest minute: {:?}", sleepiest_minute);
 
-    sleepiest_guard * sleepiest_minute
+    Ok(sleepiest_guard * sleepiest_minute)
 }
 
-fn run_2(lines: &[Line]) -> u32 {
+fn run_2(lines: &[Line]) -> Result<u32> {
     let mut current_guard = 0;
     let mut sleep_start = 0;
     let mut sleep_minute_count: HashMap<u32, HashMap<u32, u32>> = HashMap::new();
@@ -160,10 +176,9 @@ fn run_2(lines: &[Line]) -> u32 {
             Action::FallsAsleep => sleep_start = line.dt.minute(),
             Action::WakesUp => {
                 let wakeup_time = line.dt.minute();
+                sleep_time(line, sleep_start)?;
 
-                let min_lu = sleep_minute_count
-                    .entry(current_guard)
-                    .or_insert(HashMap::new());
+                let min_lu = sleep_minute_count.entry(current_guard).or_default();
                 for minute in sleep_start..wakeup_time {
                     let min_cnt = min_lu.entry(minute).or_insert(0);
                     *min_cnt += 1;
@@ -186,10 +201,14 @@ fn run_2(lines: &[Line]) -> u32 {
         }
     }
 
+    if minute_sleep == 0 {
+        return Err(Error::new("no guard ever falls asleep"));
+    }
+
     // println!("Sleepiest: {:?}", sleepiest_guard);
     // println!("Sleepiest minute: {:?}", sleepiest_minute);
 
-    sleepiest_guard * sleepiest_minute
+    Ok(sleepiest_guard * sleepiest_minute)
 }
 
 #[cfg(test)]
@@ -202,6 +221,7 @@ mod tests {
             date_time("[1518-09-14 00:54]"),
             Ok(("", Local.ymd(1518 + 500, 9, 14).and_hms(00, 54, 0),))
         );
+        assert!(date_time("[1518-13-14 00:54]").is_err());
     }
 
     #[test]
@@ -242,7 +262,7 @@ mod tests {
         let input = r#"[1518-09-14 00:54] wakes up
 [1518-04-15 23:58] Guard #373 begins shift
 [1518-07-25 00:53] wakes up"#;
-        let (_, mut lines) = parse(input).unwrap();
+        let mut lines = parse(input).unwrap();
 
         lines.sort();
 
@@ -282,11 +302,22 @@ mod tests {
 [1518-11-05 00:45] falls asleep
 [1518-11-05 00:55] wakes up"#;
 
-        let (_, mut lines) = parse(input).unwrap();
+        let mut lines = parse(input).unwrap();
 
         lines.sort();
 
-        assert_eq!(run_1(&lines), 240);
-        assert_eq!(run_2(&lines), 4455);
+        assert_eq!(run_1(&lines), Ok(240));
+        assert_eq!(run_2(&lines), Ok(4455));
+    }
+
+    #[test]
+    fn aoc4_errors() {
+        let e = Day4::parse("[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] naps")
+            .unwrap_err();
+        assert_eq!((e.line, e.column), (Some(2), Some(20)));
+
+        let lines = Day4::parse("[1518-11-01 00:00] Guard #10 begins shift\n").unwrap();
+        assert!(run_1(&lines).is_err());
+        assert!(run_2(&lines).is_err());
     }
 }
@@ -1,16 +1,41 @@
-use std::fs;
 use std::str;
 
-pub fn run() {
-    let input = fs::read_to_string("day5.txt").unwrap();
+use crate::error::{Error, Result};
+use crate::solution::Solution;
 
-    // remove last \n
-    println!("5:1 {}", run_1(input.clone()).len() - 1);
-    println!("5:2 {}", run_2(input.clone()) - 1);
+pub struct Day5;
+
+impl Solution for Day5 {
+    // The units of the polymer
+    type Input = Vec<u8>;
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        // remove last \n
+        let polymer = input.trim_end();
+        // The reaction works on bytes, so anything but ascii letters would break it
+        match polymer.find(|c: char| !c.is_ascii_alphabetic()) {
+            Some(idx) => Err(Error::at(
+                polymer,
+                &polymer[idx..],
+                "expected a unit a-z or A-Z",
+            )),
+            None => Ok(polymer.as_bytes().to_vec()),
+        }
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(input).len())
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        Ok(run_2(input))
+    }
 }
 
-fn run_1(input: String) -> String {
-    let mut var = input.into_bytes();
+fn run_1(units: &[u8]) -> String {
+    let mut var = units.to_vec();
 
     let mut i = 0;
     // println!("var.len() {}", var.len());
@@ -23,9 +48,7 @@ fn run_1(input: String) -> String {
             var.remove(i);
             // println!("2: {}", str::from_utf8(&var).unwrap());
             // println!("i: {}", i);
-            if i > 0 {
-                i -= 1;
-            }
+            i = i.saturating_sub(1);
         } else {
             i += 1;
         }
@@ -34,15 +57,15 @@ fn run_1(input: String) -> String {
     str::from_utf8(&var).unwrap().to_string()
 }
 
-fn run_2(input: String) -> usize {
-    let letters = "abcdefghijklmnopqrstuvwxyz";
-    let mut min_len = input.len();
-    for c in letters.chars() {
-        let filter_input: String = input
-            .chars()
-            .filter(|ic| (*ic != c) & (*ic != c.to_ascii_uppercase()))
+fn run_2(units: &[u8]) -> usize {
+    let mut min_len = units.len();
+    for c in b'a'..=b'z' {
+        let filter_input: Vec<u8> = units
+            .iter()
+            .filter(|u| u.to_ascii_lowercase() != c)
+            .cloned()
             .collect();
-        let l = run_1(filter_input).len();
+        let l = run_1(&filter_input).len();
         if l < min_len {
             min_len = l;
         }
@@ -55,14 +78,20 @@ mod tests {
     use super::*;
     #[test]
     fn aoc5_run_1() {
-        assert_eq!("dabCBAcaDA", run_1("dabAcCaCBAcCcaDA".to_string()));
-        assert_eq!("ac", run_1("abBc".to_string()));
-        assert_eq!("ac", run_1("aBbc".to_string()));
-        assert_eq!("", run_1("CaBbAc".to_string()));
+        assert_eq!("dabCBAcaDA", run_1(b"dabAcCaCBAcCcaDA"));
+        assert_eq!("ac", run_1(b"abBc"));
+        assert_eq!("ac", run_1(b"aBbc"));
+        assert_eq!("", run_1(b"CaBbAc"));
     }
 
     #[test]
     fn aoc5_run_2() {
-        assert_eq!(4, run_2("dabAcCaCBAcCcaDA".to_string()));
+        assert_eq!(4, run_2(b"dabAcCaCBAcCcaDA"));
+    }
+
+    #[test]
+    fn aoc5_parse() {
+        assert_eq!(Day5::parse("dabA\n"), Ok(b"dabA".to_vec()));
+        assert_eq!(Day5::parse("dab1A").unwrap_err().column, Some(4));
     }
 }
@@ -6,11 +6,13 @@ use nom::multi::many1;
 use nom::sequence::delimited;
 use nom::*;
 use std::collections::{HashMap, HashSet};
-use std::fs;
 use std::iter::FromIterator;
 
+use crate::error::{parse_all, Error, Result};
+use crate::solution::Solution;
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
-struct Coord {
+pub struct Coord {
     x: i32,
     y: i32,
 }
@@ -25,20 +27,33 @@ fn parse(i: &str) -> IResult<&str, Vec<Coord>> {
     many1(parse_coord)(i)
 }
 
-pub fn run() {
-    let input = fs::read_to_string("day6.txt").unwrap();
-    println!("6:1: {}", run_1(&input));
-    println!("6:2: {}", run_2(&input, 10000));
+pub struct Day6;
+
+impl Solution for Day6 {
+    type Input = Vec<Coord>;
+    type Part1 = u32;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_all(input, parse(input))
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        run_1(input)
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        Ok(run_2(input, 10000))
+    }
 }
 
 fn distance(a: &Coord, b: &Coord) -> u32 {
     ((a.x - b.x).abs() + (a.y - b.y).abs()) as u32
 }
 
-fn run_1(input: &str) -> u32 {
-    let (_, coords) = parse(input).unwrap();
-    let (c_min, c_max) = bounding_box(&coords);
-    let mut non_borders: HashSet<Coord> = HashSet::from_iter(coords.clone());
+fn run_1(coords: &[Coord]) -> Result<u32> {
+    let (c_min, c_max) = bounding_box(coords);
+    let mut non_borders: HashSet<Coord> = HashSet::from_iter(coords.iter().cloned());
     let mut areas = HashMap::new();
     for c in coords.iter() {
         areas.insert(c, 0);
@@ -48,7 +63,7 @@ fn run_1(input: &str) -> u32 {
             let on_bounds = x == c_min.x || x >= c_max.x || y == c_min.y || y >= c_max.y;
             let a = Coord { x, y };
 
-            let mut min_dist = u32::max_value();
+            let mut min_dist = u32::MAX;
             let mut current_node = None;
 
             for b in coords.iter() {
@@ -61,15 +76,12 @@ fn run_1(input: &str) -> u32 {
                     current_node = None;
                 }
             }
-            match current_node {
-                Some(n) => {
-                    if on_bounds {
-                        non_borders.remove(current_node.unwrap());
-                    }
-                    let old = areas.get(n).unwrap().clone();
-                    areas.insert(n, old + 1);
+            if let Some(n) = current_node {
+                if on_bounds {
+                    non_borders.remove(n);
                 }
-                None => (),
+                let old = *areas.get(n).unwrap();
+                areas.insert(n, old + 1);
             }
         }
     }
@@ -77,12 +89,12 @@ fn run_1(input: &str) -> u32 {
         .iter()
         .filter(|(c, _)| non_borders.contains(c))
         .max_by(|(_, a), (_, b)| a.cmp(b));
-    *max.unwrap().1
+    max.map(|(_, area)| *area)
+        .ok_or_else(|| Error::new("all areas are infinite"))
 }
 
-fn run_2(input: &str, max_distance: u32) -> u32 {
-    let (_, coords) = parse(input).unwrap();
-    let (c_min, c_max) = bounding_box(&coords);
+fn run_2(coords: &[Coord], max_distance: u32) -> u32 {
+    let (c_min, c_max) = bounding_box(coords);
     let mut region_area = 0;
     for y in c_min.y..(c_max.y + 1) {
         for x in c_min.x..(c_max.x + 1) {
@@ -99,9 +111,9 @@ fn run_2(input: &str, max_distance: u32) -> u32 {
 }
 
 fn bounding_box(coords: &[Coord]) -> (Coord, Coord) {
-    let mut x_min = i32::max_value();
+    let mut x_min = i32::MAX;
     let mut x_max = 0;
-    let mut y_min = i32::max_value();
+    let mut y_min = i32::MAX;
     let mut y_max = 0;
     for c in coords.iter() {
         x_min = std::cmp::min(x_min, c.x);
@@ -132,7 +144,7 @@ mod tests {
        3, 4
        5, 5
        8, 9"#;
-        assert_eq!(run_1(input), 17);
+        assert_eq!(run_1(&Day6::parse(input).unwrap()), Ok(17));
     }
 
     #[test]
@@ -143,6 +155,7 @@ mod tests {
        3, 4
        5, 5
        8, 9"#;
-        assert_eq!(run_2(input, 32), 16);
+        assert_eq!(run_2(&Day6::parse(input).unwrap(), 32), 16);
+        assert!(Day6::parse("1, 1\n1; 6").is_err());
     }
 }
@@ -1,38 +1,59 @@
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::collections::{BTreeSet, HashMap};
-use std::fs;
 use std::str;
 
-pub fn run() {
-    let input = fs::read_to_string("day7.txt").unwrap();
-    println!("day7-1: {}", run_1(&input));
-    // 897 // too high
-    println!("day7-2: {}", run_2(&input, 5, 60));
+use crate::error::{parse_lines, Error, Result};
+use crate::solution::Solution;
+
+pub struct Day7;
+
+impl Solution for Day7 {
+    // Each step and a step that depends on it
+    type Input = Vec<(String, String)>;
+    type Part1 = String;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        parse_lines(input, parse_line)
+    }
+
+    fn part_one(input: &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(input))
+    }
+
+    fn part_two(input: &Self::Input) -> Result<Self::Part2> {
+        // 897 // too high
+        Ok(run_2(input, 5, 60))
+    }
 }
 
-fn parse_line(line: &str) -> (String, String) {
+fn parse_line(line: &str) -> Result<(String, String)> {
     lazy_static! {
-        static ref RE: Regex = Regex::new(r#"Step\s(\w).*step\s(\w).*\."#).unwrap();
+        static ref RE: Regex = Regex::new(r#"Step\s([A-Z]).*step\s([A-Z]).*\."#).unwrap();
     }
-    let c = RE.captures(line).unwrap();
+    let c = RE.captures(line).ok_or_else(|| {
+        Error::new(format!(
+            "expected 'Step X must be finished before step Y can begin.', found '{}'",
+            line
+        ))
+    })?;
     let c1 = c[1].to_string();
     let c2 = c[2].to_string();
-    (c1, c2)
+    Ok((c1, c2))
 }
 
-pub fn run_1(input: &str) -> String {
-    let components: Vec<(String, String)> = input.lines().map(parse_line).collect();
+pub fn run_1(components: &[(String, String)]) -> String {
     let mut lookup: HashMap<String, BTreeSet<String>> = HashMap::new();
     let mut open = BTreeSet::new();
     let mut closed = BTreeSet::new();
-    for (c1, c2) in components {
+    for (c1, c2) in components.iter().cloned() {
         if !closed.contains(&c1) {
             open.insert(c1.clone());
         }
         open.remove(&c2);
         closed.insert(c2.clone());
-        let s = lookup.entry(c1).or_insert(BTreeSet::new());
+        let s = lookup.entry(c1).or_default();
         s.insert(c2);
     }
 
@@ -46,7 +67,7 @@ pub fn run_1(input: &str) -> String {
                 open.insert(v.clone());
             }
         }
-        res = res.into_iter().filter(|c| *c != o).collect();
+        res.retain(|c| *c != o);
         res.push(o);
     }
 
@@ -74,32 +95,20 @@ impl WorkItem {
     }
 }
 
-fn print_workers(sec: u32, workers: &[Option<WorkItem>]) {
-    print!("{:03} - ", sec);
-    for w in workers {
-        match w {
-            None => print!(". "),
-            Some(wi) => print!("{} ", &wi.name),
-        }
-    }
-    println!("");
-}
-
-pub fn run_2(input: &str, num_workers: usize, base_cost: usize) -> u32 {
-    let components: Vec<(String, String)> = input.lines().map(parse_line).collect();
+pub fn run_2(components: &[(String, String)], num_workers: usize, base_cost: usize) -> u32 {
     let mut lookup: HashMap<String, BTreeSet<String>> = HashMap::new();
     let mut required: HashMap<String, BTreeSet<String>> = HashMap::new();
     let mut open = BTreeSet::new();
     let mut closed = BTreeSet::new();
-    for (c1, c2) in components {
+    for (c1, c2) in components.iter().cloned() {
         if !closed.contains(&c1) {
             open.insert(c1.clone());
         }
         open.remove(&c2);
         closed.insert(c2.clone());
-        let s = lookup.entry(c1.clone()).or_insert(BTreeSet::new());
+        let s = lookup.entry(c1.clone()).or_default();
         s.insert(c2.clone());
-        let s = required.entry(c2).or_insert(BTreeSet::new());
+        let s = required.entry(c2).or_default();
         s.insert(c1);
     }
 
@@ -117,16 +126,12 @@ pub fn run_2(input: &str, num_workers: usize, base_cost: usize) -> u32 {
     }
 
     let mut sum = 0;
-    let mut sec = 0;
 
     loop {
         if workers.iter().all(|i| i.is_none()) {
             break;
         }
         sum += 1;
-        sec += 1;
-
-        print_workers(sec, &workers);
 
         for w in workers.iter_mut() {
             if let Some(wi) = w {
@@ -179,9 +184,10 @@ mod tests {
     #[test]
     fn aoc7_parse_line() {
         assert_eq!(
-            ("C".to_string(), "F".to_string()),
+            Ok(("C".to_string(), "F".to_string())),
             parse_line("Step C must be finished before step F can begin.")
         );
+        assert!(parse_line("Step c must be finished before step F can begin.").is_err());
     }
     #[test]
     fn aoc7_run_step_cost() {
@@ -198,7 +204,7 @@ Step A must be finished before step D can begin.
 Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
 Step F must be finished before step E can begin."#;
-        assert_eq!("CABDFE", run_1(input));
+        assert_eq!("CABDFE", run_1(&Day7::parse(input).unwrap()));
     }
 
     #[test]
@@ -210,6 +216,6 @@ Step A must be finished before step D can begin.
 Step B must be finished before step E can begin.
 Step D must be finished before step E can begin.
 Step F must be finished before step E can begin."#;
-        assert_eq!(15, run_2(input, 2, 0));
+        assert_eq!(15, run_2(&Day7::parse(input).unwrap(), 2, 0));
     }
 }
@@ -1,50 +1,84 @@
-use std::fs;
+use crate::error::{Error, Result};
+use crate::solution::Solution;
 
-pub fn run() {
-    let input = fs::read_to_string("day8.txt").unwrap();
-    println!("day8-1: {}", run_1(&input[0..(input.len() - 1)]));
-    println!("day8-2: {}", run_2(&input[0..(input.len() - 1)]));
+pub struct Day8;
+
+impl Solution for Day8 {
+    // The root of the tree
+    type Input = Node;
+    type Part1 = u32;
+    type Part2 = u32;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        let (root, _) = parse_child(&to_vec(input.trim_end())?, 0)?;
+        Ok(root)
+    }
+
+    fn part_one(root: &Self::Input) -> Result<Self::Part1> {
+        Ok(sum_metadata(root))
+    }
+
+    fn part_two(root: &Self::Input) -> Result<Self::Part2> {
+        Ok(value(root))
+    }
 }
 
 #[derive(Debug)]
-struct Node {
+pub struct Node {
     metadata: Vec<u32>,
     children: Vec<Node>,
 }
 
-fn to_vec(input: &str) -> Vec<u32> {
+fn to_vec(input: &str) -> Result<Vec<u32>> {
     input
-        .split(" ")
-        .map(|s| s.parse::<u32>().unwrap())
+        .split(' ')
+        .map(|s| {
+            s.parse::<u32>().map_err(|_| {
+                let offset = s.as_ptr() as usize - input.as_ptr() as usize;
+                Error::at(
+                    input,
+                    &input[offset..],
+                    format!("expected a number, found '{}'", s),
+                )
+            })
+        })
         .collect()
 }
 
-fn parse_child(nodes: &[u32], mut idx: usize) -> (Node, usize) {
-    let no_children = nodes[idx];
-    idx += 1;
-    let no_metdata = nodes[idx];
-    idx += 1;
+fn next(nodes: &[u32], idx: &mut usize) -> Result<u32> {
+    let v = nodes.get(*idx).ok_or_else(|| {
+        Error::new(format!(
+            "unexpected end of input, expected more than {} numbers",
+            nodes.len()
+        ))
+    })?;
+    *idx += 1;
+    Ok(*v)
+}
+
+fn parse_child(nodes: &[u32], mut idx: usize) -> Result<(Node, usize)> {
+    let no_children = next(nodes, &mut idx)?;
+    let no_metdata = next(nodes, &mut idx)?;
 
     let mut children = Vec::new();
     for _ in 0..no_children {
-        let (c, new_idx) = parse_child(nodes, idx);
+        let (c, new_idx) = parse_child(nodes, idx)?;
         children.push(c);
         idx = new_idx;
     }
 
     let mut metadata = Vec::new();
     for _ in 0..no_metdata {
-        metadata.push(nodes[idx]);
-        idx += 1;
+        metadata.push(next(nodes, &mut idx)?);
     }
 
-    (
+    Ok((
         Node {
-            metadata: metadata,
-            children: children,
+            metadata,
+            children,
         },
         idx,
-    )
+    ))
 }
 
 fn sum_metadata(child: &Node) -> u32 {
@@ -55,54 +89,45 @@ fn sum_metadata(child: &Node) -> u32 {
         .fold(0, |sum, c| sum + sum_metadata(c))
 }
 
-fn run_1(input: &str) -> u32 {
-    let node_input = to_vec(input);
-
-    let (child, _) = parse_child(&node_input, 0);
-
-    sum_metadata(&child)
-}
-
 fn value(node: &Node) -> u32 {
-    if node.children.len() == 0 {
+    if node.children.is_empty() {
         node.metadata.iter().sum()
     } else {
         let mut sum = 0;
         for md in node.metadata.iter() {
-            let idx = *md as usize - 1;
-            if idx < node.children.len() {
-                sum += value(&node.children[idx]);
+            // Metadata 0 does not refer to any child
+            if let Some(child) = (*md as usize)
+                .checked_sub(1)
+                .and_then(|idx| node.children.get(idx))
+            {
+                sum += value(child);
             }
         }
         sum
     }
 }
 
-fn run_2(input: &str) -> u32 {
-    let node_input = to_vec(input);
-
-    let (child, _) = parse_child(&node_input, 0);
-
-    value(&child)
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn aoc8_to_vec() {
-        assert_eq!(to_vec("1 2 3 4"), [1, 2, 3, 4]);
-        assert_eq!(to_vec("10 20 30 40"), [10, 20, 30, 40]);
+        assert_eq!(to_vec("1 2 3 4"), Ok(vec![1, 2, 3, 4]));
+        assert_eq!(to_vec("10 20 30 40"), Ok(vec![10, 20, 30, 40]));
+        assert_eq!(to_vec("10 2x 30").unwrap_err().column, Some(4));
     }
 
     #[test]
     fn aoc8_run_1() {
-        assert_eq!(run_1("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2"), 138);
+        let root = Day8::parse("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2\n").unwrap();
+        assert_eq!(sum_metadata(&root), 138);
+        assert!(Day8::parse("2 3 0 3 10").is_err());
     }
 
     #[test]
     fn aoc8_run_2() {
-        assert_eq!(run_2("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2"), 66);
+        let root = Day8::parse("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        assert_eq!(value(&root), 66);
     }
 }
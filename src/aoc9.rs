@@ -1,6 +1,41 @@
-pub fn run() {
-    println!("day9-1: {}", run_1(435, 71184));
-    println!("day9-2: {}", run_1(435, 71184 * 100));
+use nom::bytes::complete::tag;
+use nom::IResult;
+
+use crate::error::{parse_all, Error, Result};
+use crate::helper::usize_val;
+use crate::solution::Solution;
+
+pub struct Day9;
+
+impl Solution for Day9 {
+    type Input = (usize, usize);
+    type Part1 = usize;
+    type Part2 = usize;
+
+    fn parse(input: &str) -> Result<Self::Input> {
+        let (no_players, last_marble) = parse_all(input, parse(input))?;
+        if no_players == 0 {
+            return Err(Error::at_line(1, "there must be at least one player"));
+        }
+        Ok((no_players, last_marble))
+    }
+
+    fn part_one(&(no_players, last_marble): &Self::Input) -> Result<Self::Part1> {
+        Ok(run_1(no_players, last_marble))
+    }
+
+    fn part_two(&(no_players, last_marble): &Self::Input) -> Result<Self::Part2> {
+        Ok(run_1(no_players, last_marble * 100))
+    }
+}
+
+// 435 players; last marble is worth 71184 points
+fn parse(i: &str) -> IResult<&str, (usize, usize)> {
+    let (i, no_players) = usize_val(i)?;
+    let (i, _) = tag(" players; last marble is worth ")(i)?;
+    let (i, last_marble) = usize_val(i)?;
+    let (i, _) = tag(" points")(i)?;
+    Ok((i, (no_players, last_marble)))
 }
 
 fn run_1(no_players: usize, last_marble: usize) -> usize {
@@ -36,6 +71,14 @@ fn run_1(no_players: usize, last_marble: usize) -> usize {
 mod tests {
     use super::*;
 
+    #[test]
+    fn aoc9_parse() {
+        assert_eq!(
+            parse("435 players; last marble is worth 71184 points\n"),
+            Ok(("\n", (435, 71184)))
+        );
+    }
+
     #[test]
     fn aoc9_run_1() {
         assert_eq!(run_1(10, 25), 32);
@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use nom::bytes::complete::tag;
+use nom::character::complete::{alpha1, space0, space1};
+use nom::combinator::verify;
+use nom::IResult;
+
+use crate::error::{finish, parse_all, Error, Result};
+use crate::helper::usize_val;
+use crate::opcodes::{Instruction, LoadError, Opcode, Vm};
+
+// ElfCode source is an optional `#ip N` line followed by one `op a b c` instruction per line,
+// blank lines and everything after a `;` are ignored.
+//
+// #ip 0
+// seti 5 0 1 ; R1 = 5
+// addr 1 2 3
+
+// Number of registers available to a program with a bound ip
+pub const REGISTERS: usize = 6;
+
+// Number of registers available to a program without one, like the day 16 samples
+pub const SAMPLE_REGISTERS: usize = 4;
+
+// Numeric opcode to operation, as found from the day 16 samples
+pub type Mapping = HashMap<usize, Opcode>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Line {
+    // 1-based line in the source
+    pub number: usize,
+    pub instruction: Instruction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub ip_reg: Option<usize>,
+    pub lines: Vec<Line>,
+}
+
+impl Listing {
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.lines.iter().map(|l| l.instruction).collect()
+    }
+
+    // The number of registers, which follows from the dialect: programs with a bound ip have
+    // REGISTERS of them and the others SAMPLE_REGISTERS
+    pub fn width(&self) -> usize {
+        match self.ip_reg {
+            Some(_) => REGISTERS,
+            None => SAMPLE_REGISTERS,
+        }
+    }
+
+    // A VM loaded with the program, an instruction that can not be loaded is reported at its
+    // source line
+    pub fn vm(&self) -> Result<Vm> {
+        Vm::new(self.instructions(), self.ip_reg, self.width()).map_err(|e| match e {
+            LoadError::Register { ip, .. } => Error {
+                line: self.line_of(ip),
+                ..Error::from(e)
+            },
+            e => Error::from(e),
+        })
+    }
+
+    // Errors at the first instruction naming a register outside of the width
+    pub fn validate(&self) -> Result<()> {
+        let width = self.width();
+        for l in self.lines.iter() {
+            if let Some(r) = l.instruction.invalid_register(width) {
+                return Err(Error::at_line(
+                    l.number,
+                    format!(
+                        "'{}' uses register {}, there are only {}",
+                        l.instruction, r, width
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // The source line of the instruction at `ip`
+    pub fn line_of(&self, ip: usize) -> Option<usize> {
+        self.lines.get(ip).map(|l| l.number)
+    }
+}
+
+// Canonical ElfCode, single spaced without comments
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ip) = self.ip_reg {
+            writeln!(f, "#ip {}", ip)?;
+        }
+        for l in self.lines.iter() {
+            writeln!(f, "{}", l.instruction)?;
+        }
+        Ok(())
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    match line.find(';') {
+        Some(i) => &line[..i],
+        None => line,
+    }
+}
+
+fn parse_ip(i: &str) -> IResult<&str, usize> {
+    let (i, _) = space0(i)?;
+    let (i, _) = tag("#ip")(i)?;
+    let (i, _) = space1(i)?;
+    verify(usize_val, |ip| *ip < REGISTERS)(i)
+}
+
+fn operands(i: &str) -> IResult<&str, (usize, usize, usize)> {
+    let (i, _) = space1(i)?;
+    let (i, a) = usize_val(i)?;
+    let (i, _) = space1(i)?;
+    let (i, b) = usize_val(i)?;
+    let (i, _) = space1(i)?;
+    let (i, c) = usize_val(i)?;
+    Ok((i, (a, b, c)))
+}
+
+fn parse_instruction(line: &str) -> Result<Instruction> {
+    let start = line.trim_start();
+    let (rest, mnemonic) = finish(line, alpha1(start))?;
+    let op = Opcode::from_mnemonic(mnemonic)
+        .ok_or_else(|| Error::at(line, start, format!("unknown operation '{}'", mnemonic)))?;
+    let (a, b, c) = parse_all(line, operands(rest))?;
+    Ok(Instruction::new(op, a, b, c))
+}
+
+// Runs `f` on every line that is not blank or a comment, errors are moved to the right line
+fn source_lines<T>(
+    input: &str,
+    mut f: impl FnMut(usize, &str) -> Result<Option<T>>,
+) -> Result<Vec<T>> {
+    let mut res = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let code = strip_comment(line).trim_end();
+        if code.trim().is_empty() {
+            continue;
+        }
+        let number = i + 1;
+        let v = f(number, code).map_err(|e| Error {
+            line: Some(number),
+            ..e
+        })?;
+        res.extend(v);
+    }
+    Ok(res)
+}
+
+pub fn parse(input: &str) -> Result<Listing> {
+    let mut ip_reg = None;
+    let lines = source_lines(input, |number, code| {
+        if code.trim_start().starts_with('#') {
+            if ip_reg.is_some() {
+                return Err(Error::new("the ip can only be bound once"));
+            }
+            ip_reg = Some(parse_all(code, parse_ip(code))?);
+            return Ok(None);
+        }
+        let instruction = parse_instruction(code)?;
+        Ok(Some(Line {
+            number,
+            instruction,
+        }))
+    })?;
+    let listing = Listing { ip_reg, lines };
+    listing.validate()?;
+    Ok(listing)
+}
+
+fn numeric(i: &str) -> IResult<&str, (usize, (usize, usize, usize))> {
+    let (i, _) = space0(i)?;
+    let (i, opcode) = usize_val(i)?;
+    let (i, abc) = operands(i)?;
+    Ok((i, (opcode, abc)))
+}
+
+// Translates a program with numeric opcodes, like the second half of the day 16 input
+pub fn translate(input: &str, mapping: &Mapping) -> Result<Listing> {
+    let lines = source_lines(input, |number, code| {
+        let (opcode, (a, b, c)) = parse_all(code, numeric(code))?;
+        let op = mapping.get(&opcode).ok_or_else(|| {
+            Error::at(
+                code,
+                code.trim_start(),
+                format!("opcode {} is not mapped to an operation", opcode),
+            )
+        })?;
+        Ok(Some(Line {
+            number,
+            instruction: Instruction::new(*op, a, b, c),
+        }))
+    })?;
+    let listing = Listing {
+        ip_reg: None,
+        lines,
+    };
+    listing.validate()?;
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5
+";
+
+    #[test]
+    fn asm_parse() {
+        let listing = parse(EXAMPLE).unwrap();
+        assert_eq!(listing.ip_reg, Some(0));
+        assert_eq!(
+            listing.lines[0],
+            Line {
+                number: 2,
+                instruction: Instruction::new(Opcode::Seti, 5, 0, 1)
+            }
+        );
+
+        let listing = parse("; comment\n\n  addi 2 16 2 ; jump\neqrr 1   3 1\n").unwrap();
+        assert_eq!(listing.ip_reg, None);
+        assert_eq!(listing.line_of(0), Some(3));
+        assert_eq!(listing.line_of(1), Some(4));
+        assert_eq!(listing.to_string(), "addi 2 16 2\neqrr 1 3 1\n");
+
+        let e = parse("#ip 0\nseti 5 0 1\nnoop 6 0 2\n").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(3), Some(1)));
+        assert_eq!(e.message, "unknown operation 'noop'");
+        let e = parse("seti 5 0\n").unwrap_err();
+        assert_eq!((e.line, e.column), (Some(1), Some(9)));
+        assert!(parse("#ip 6\n").is_err());
+        let e = parse("#ip 0\nseti 9 9 5\naddr 1 6 2\n").unwrap_err();
+        assert_eq!(e.line, Some(3));
+        assert_eq!(e.message, "'addr 1 6 2' uses register 6, there are only 6");
+        // Without a bound ip there are only as many registers as in the samples
+        let e = parse("seti 9 9 4\n").unwrap_err();
+        assert_eq!(e.message, "'seti 9 9 4' uses register 4, there are only 4");
+        assert_eq!(parse("#ip 0\n").unwrap().vm().unwrap().regs, [0; 6]);
+        assert_eq!(parse("").unwrap().vm().unwrap().regs, [0; 4]);
+        // A listing changed after parsing is checked again when it is loaded
+        let mut listing = parse("#ip 0\n\nseti 9 9 5\n").unwrap();
+        listing.lines[0].instruction.c = 6;
+        let e = listing.vm().unwrap_err();
+        assert_eq!(e.line, Some(3));
+        assert_eq!(
+            e.message,
+            "'seti 9 9 6' at ip 0 uses register 6, which does not exist"
+        );
+        assert!(parse("#ip 1\n#ip 2\n").is_err());
+    }
+
+    #[test]
+    fn asm_round_trip() {
+        let listing = parse(EXAMPLE).unwrap();
+        assert_eq!(listing.to_string(), EXAMPLE);
+        assert_eq!(parse(&listing.to_string()), Ok(listing));
+
+        for input in [
+            "",
+            "gtir 7 0 3\n",
+            &std::fs::read_to_string("inputs/day19.txt").unwrap(),
+        ]
+        .iter()
+        {
+            let listing = parse(input).unwrap();
+            assert_eq!(parse(&listing.to_string()), Ok(listing));
+        }
+
+        // Comments and spacing are not kept, but the instructions are
+        let listing = parse("  #ip   2 ; bind\n\nbori 1 2 3;x\n").unwrap();
+        let printed = parse(&listing.to_string()).unwrap();
+        assert_eq!(printed.to_string(), "#ip 2\nbori 1 2 3\n");
+        assert_eq!(printed.ip_reg, listing.ip_reg);
+        assert_eq!(printed.instructions(), listing.instructions());
+    }
+
+    #[test]
+    fn asm_translate() {
+        let mapping: Mapping = Opcode::ALL.iter().cloned().enumerate().collect();
+        let listing = translate("9 2 1 2\n\n0 1 2 3\n", &mapping).unwrap();
+        assert_eq!(listing.to_string(), "seti 2 1 2\naddr 1 2 3\n");
+        assert_eq!(listing.line_of(1), Some(3));
+        assert_eq!(
+            parse(&listing.to_string()).unwrap().instructions(),
+            listing.instructions()
+        );
+
+        let e = translate("1 2 3 4\n16 2 3 4\n", &mapping).unwrap_err();
+        assert_eq!((e.line, e.column), (Some(2), Some(1)));
+        assert!(translate("1 2 3\n", &mapping).is_err());
+        let e = translate("1 2 3 4\n", &mapping).unwrap_err();
+        assert_eq!(e.message, "'addi 2 3 4' uses register 4, there are only 4");
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::{Duration, Instant};
+
+use crate::asm::Listing;
+use crate::compile::Compiled;
+use crate::error::Result;
+use crate::opcodes::{self, Overflow, Registers};
+
+// How fast the ways of running ElfCode execute a program, none of them use the idioms
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurement {
+    pub engine: &'static str,
+    pub steps: usize,
+    pub elapsed: Duration,
+    pub regs: Registers,
+}
+
+impl Measurement {
+    pub fn per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.steps as f64 / secs
+        }
+    }
+}
+
+type Operation = fn(&mut [usize], usize, usize, usize);
+
+// The operation with the mnemonic, compared string by string like the interpreter the VM
+// replaced did
+fn by_mnemonic(mnemonic: &str) -> Option<Operation> {
+    let f: Operation = match mnemonic {
+        "addr" => opcodes::addr,
+        "addi" => opcodes::addi,
+        "mulr" => opcodes::mulr,
+        "muli" => opcodes::muli,
+        "banr" => opcodes::banr,
+        "bani" => opcodes::bani,
+        "borr" => opcodes::borr,
+        "bori" => opcodes::bori,
+        "setr" => opcodes::setr,
+        "seti" => opcodes::seti,
+        "gtir" => opcodes::gtir,
+        "gtri" => opcodes::gtri,
+        "gtrr" => opcodes::gtrr,
+        "eqir" => opcodes::eqir,
+        "eqri" => opcodes::eqri,
+        "eqrr" => opcodes::eqrr,
+        _ => return None,
+    };
+    Some(f)
+}
+
+// The interpreter the VM replaced, it compares the mnemonic of every executed instruction. An
+// unknown mnemonic halts the program.
+fn string_match(listing: &Listing, regs: &mut Registers, max: usize) -> usize {
+    let program: Vec<(String, usize, usize, usize)> = listing
+        .instructions()
+        .iter()
+        .map(|i| (i.op.to_string(), i.a, i.b, i.c))
+        .collect();
+    let mut ip = 0;
+    let mut steps = 0;
+    while ip < program.len() && steps < max {
+        if let Some(r) = listing.ip_reg {
+            regs[r] = ip;
+        }
+        let (op, a, b, c) = &program[ip];
+        let f = match by_mnemonic(op) {
+            Some(f) => f,
+            None => break,
+        };
+        f(regs, *a, *b, *c);
+        if let Some(r) = listing.ip_reg {
+            ip = regs[r];
+        }
+        // Like the VM, an ip that does not fit halts the program
+        ip = ip.saturating_add(1);
+        steps += 1;
+    }
+    steps
+}
+
+fn measure(engine: &'static str, f: impl FnOnce() -> (usize, Registers)) -> Measurement {
+    let start = Instant::now();
+    let (steps, regs) = f();
+    Measurement {
+        engine,
+        steps,
+        elapsed: start.elapsed(),
+        regs,
+    }
+}
+
+// Runs the program with each engine, with r0 set to `r0` and until it halts or has run `max`
+// instructions. Decoding the program is part of the measured time. Overflows wrap like they do
+// in the string matching loop, and a program that loads only names registers that exist, so
+// nothing faults.
+pub fn run(listing: &Listing, r0: usize, max: usize) -> Result<Vec<Measurement>> {
+    const NO_FAULT: &str = "a loaded program with wrapping arithmetic does not fault";
+    let mut vm = listing.vm()?;
+    vm.regs[0] = r0;
+    vm.overflow = Overflow::Wrap;
+    let mut regs = vm.regs.clone();
+    let mut compiled = vm.clone();
+    Ok(vec![
+        measure("string match", || {
+            (string_match(listing, &mut regs, max), regs)
+        }),
+        measure("vm", || (vm.run_steps(max).expect(NO_FAULT), vm.regs)),
+        measure("compiled", || {
+            let steps = match Compiled::new(&compiled) {
+                Some(c) => c.run(&mut compiled, max),
+                None => compiled.run_steps(max),
+            };
+            (steps.expect(NO_FAULT), compiled.regs)
+        }),
+    ])
+}
+
+pub fn report(measurements: &[Measurement]) -> String {
+    let mut s = format!(
+        "{:<14} {:>12} {:>10} {:>14} {:>8}\n",
+        "engine", "steps", "ms", "steps/s", "speedup"
+    );
+    let base = measurements.first().map_or(0.0, |m| m.per_second());
+    for m in measurements {
+        let speedup = if base == 0.0 {
+            0.0
+        } else {
+            m.per_second() / base
+        };
+        s += &format!(
+            "{:<14} {:>12} {:>10.1} {:>14.0} {:>7.2}x\n",
+            m.engine,
+            m.steps,
+            m.elapsed.as_secs_f64() * 1000.0,
+            m.per_second(),
+            speedup
+        );
+    }
+    if measurements.windows(2).any(|w| w[0].regs != w[1].regs) {
+        s += "the engines do not agree on the registers\n";
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn bench_run() {
+        let listing = asm::parse(&std::fs::read_to_string("inputs/day19.txt").unwrap()).unwrap();
+        let measurements = run(&listing, 0, 50_000).unwrap();
+        let engines: Vec<&str> = measurements.iter().map(|m| m.engine).collect();
+        assert_eq!(engines, ["string match", "vm", "compiled"]);
+        assert!(measurements.iter().all(|m| m.steps == 50_000));
+        assert!(measurements.iter().all(|m| m.regs == measurements[0].regs));
+
+        let report = report(&measurements);
+        assert_eq!(report.lines().count(), 4);
+        assert!(report.starts_with("engine"));
+    }
+
+    #[test]
+    fn bench_string_match() {
+        assert!(by_mnemonic("seti").is_some());
+        assert!(by_mnemonic("nope").is_none());
+
+        // Jumps to the last possible ip, which halts instead of wrapping around to 0
+        let listing = asm::parse("#ip 0\nseti 18446744073709551615 0 0\n").unwrap();
+        let mut regs = listing.vm().unwrap().regs;
+        assert_eq!(string_match(&listing, &mut regs, 10), 1);
+    }
+}
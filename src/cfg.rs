@@ -0,0 +1,328 @@
+use std::collections::BTreeSet;
+
+use crate::asm::Listing;
+use crate::opcodes::{Instruction, Opcode};
+
+// Where an instruction continues, found without running the program. An instruction that
+// writes the bound ip register is a jump, its target is known when it only reads the ip
+// register and immediate values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    Next,
+    // The target can be outside of the program, which halts it
+    Jump(usize),
+    // `ip += r` right after a comparison wrote r, skips the next instruction if r is 1
+    Skip(usize),
+    // Depends on a register that is not known statically
+    Computed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Target {
+    Ip(usize),
+    Halt,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    // Instructions start..end
+    pub start: usize,
+    pub end: usize,
+    pub targets: Vec<Target>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub program: Vec<Instruction>,
+    pub ip_reg: Option<usize>,
+    pub flows: Vec<Flow>,
+    pub blocks: Vec<Block>,
+}
+
+// The jump target of an instruction that only depends on the ip
+fn static_jump(i: &Instruction, ip: usize, ip_reg: usize) -> Option<usize> {
+    if i.reads().any(|r| r != ip_reg) {
+        return None;
+    }
+    let mut regs = vec![0; ip_reg.max(i.c) + 1];
+    regs[ip_reg] = ip;
+    i.execute(&mut regs);
+    regs[i.c].checked_add(1)
+}
+
+fn flows(program: &[Instruction], ip_reg: Option<usize>) -> Vec<Flow> {
+    let ip_reg = match ip_reg {
+        Some(r) => r,
+        None => return vec![Flow::Next; program.len()],
+    };
+
+    let mut flows: Vec<Flow> = program
+        .iter()
+        .enumerate()
+        .map(|(ip, i)| {
+            if i.c != ip_reg {
+                Flow::Next
+            } else {
+                static_jump(i, ip, ip_reg).map_or(Flow::Computed, Flow::Jump)
+            }
+        })
+        .collect();
+    let jump_targets: BTreeSet<usize> = flows
+        .iter()
+        .filter_map(|f| match f {
+            Flow::Jump(t) => Some(*t),
+            _ => None,
+        })
+        .collect();
+
+    // A skip needs the register to be 0 or 1, so the comparison must be the only way in
+    for ip in 1..program.len() {
+        let i = program[ip];
+        if flows[ip] != Flow::Computed || i.op != Opcode::Addr {
+            continue;
+        }
+        let other = match (i.a == ip_reg, i.b == ip_reg) {
+            (true, false) => i.b,
+            (false, true) => i.a,
+            _ => continue,
+        };
+        let prev = program[ip - 1];
+        let landed = ip >= 2 && matches!(flows[ip - 2], Flow::Skip(_));
+        if prev.op.is_comparison()
+            && prev.c == other
+            && flows[ip - 1] == Flow::Next
+            && !landed
+            && !jump_targets.contains(&ip)
+        {
+            flows[ip] = Flow::Skip(other);
+        }
+    }
+    flows
+}
+
+impl Cfg {
+    pub fn new(program: &[Instruction], ip_reg: Option<usize>) -> Self {
+        let flows = flows(program, ip_reg);
+        let mut cfg = Cfg {
+            program: program.to_vec(),
+            ip_reg,
+            flows,
+            blocks: Vec::new(),
+        };
+
+        let mut leaders = BTreeSet::new();
+        if !program.is_empty() {
+            leaders.insert(0);
+        }
+        for ip in 0..program.len() {
+            if cfg.flows[ip] == Flow::Next {
+                continue;
+            }
+            leaders.insert(ip + 1);
+            for t in cfg.targets(ip) {
+                if let Target::Ip(t) = t {
+                    leaders.insert(t);
+                }
+            }
+        }
+        leaders.insert(program.len());
+
+        let leaders: Vec<usize> = leaders
+            .into_iter()
+            .filter(|l| *l <= program.len())
+            .collect();
+        cfg.blocks = leaders
+            .windows(2)
+            .map(|w| Block {
+                start: w[0],
+                end: w[1],
+                targets: cfg.targets(w[1] - 1),
+            })
+            .collect();
+        cfg
+    }
+
+    pub fn of(listing: &Listing) -> Self {
+        Cfg::new(&listing.instructions(), listing.ip_reg)
+    }
+
+    fn target(&self, ip: usize) -> Target {
+        if ip < self.program.len() {
+            Target::Ip(ip)
+        } else {
+            Target::Halt
+        }
+    }
+
+    // Where the instruction at `ip` can continue
+    pub fn targets(&self, ip: usize) -> Vec<Target> {
+        match self.flows[ip] {
+            Flow::Next => vec![self.target(ip + 1)],
+            Flow::Jump(t) => vec![self.target(t)],
+            Flow::Skip(_) => vec![self.target(ip + 1), self.target(ip + 2)],
+            Flow::Computed => vec![Target::Unknown],
+        }
+    }
+
+    // The block that contains the instruction at `ip`
+    pub fn block_of(&self, ip: usize) -> Option<&Block> {
+        self.blocks.iter().find(|b| b.start <= ip && ip < b.end)
+    }
+
+    // Graphviz source with a box per block listing its instructions. Jumps that can not be
+    // resolved go to a separate unknown node with dashed edges.
+    pub fn to_dot(&self) -> String {
+        let mut s = String::from("digraph program {\n    node [shape=box, fontname=monospace];\n");
+        let mut halts = false;
+        let mut unknown = false;
+
+        for b in self.blocks.iter() {
+            let mut label = if b.end - b.start == 1 {
+                format!("ip {}\\l", b.start)
+            } else {
+                format!("ip {}-{}\\l", b.start, b.end - 1)
+            };
+            for i in self.program[b.start..b.end].iter() {
+                label += &format!("{}\\l", i);
+            }
+            s += &format!("    b{} [label=\"{}\"];\n", b.start, label);
+
+            let last = b.end - 1;
+            for (n, t) in b.targets.iter().enumerate() {
+                let attrs = match (self.flows[last], n) {
+                    (Flow::Skip(r), 0) => format!(" [label=\"r{} == 0\"]", r),
+                    (Flow::Skip(r), _) => format!(" [label=\"r{} == 1\"]", r),
+                    (Flow::Computed, _) => " [style=dashed]".to_owned(),
+                    _ => String::new(),
+                };
+                let to = match t {
+                    Target::Ip(ip) => format!("b{}", ip),
+                    Target::Halt => {
+                        halts = true;
+                        "halt".to_owned()
+                    }
+                    Target::Unknown => {
+                        unknown = true;
+                        "unknown".to_owned()
+                    }
+                };
+                s += &format!("    b{} -> {}{};\n", b.start, to, attrs);
+            }
+        }
+
+        if halts {
+            s += "    halt [shape=doublecircle];\n";
+        }
+        if unknown {
+            s += "    unknown [shape=diamond, style=dashed, label=\"?\"];\n";
+        }
+        s += "}\n";
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn cfg_flows() {
+        let listing = asm::parse(
+            "#ip 2
+seti 5 0 2
+eqrr 1 3 1
+addr 1 2 2
+addi 2 1 2
+addr 2 0 2
+mulr 2 2 2
+setr 2 0 2
+",
+        )
+        .unwrap();
+        let cfg = Cfg::of(&listing);
+        assert_eq!(
+            cfg.flows,
+            [
+                Flow::Jump(6),
+                Flow::Next,
+                Flow::Skip(1),
+                Flow::Jump(5),
+                Flow::Computed,
+                Flow::Jump(26),
+                Flow::Jump(7),
+            ]
+        );
+        assert_eq!(cfg.targets(2), [Target::Ip(3), Target::Ip(4)]);
+        assert_eq!(cfg.targets(5), [Target::Halt]);
+        assert_eq!(cfg.targets(4), [Target::Unknown]);
+
+        // Without a bound ip every instruction continues with the next
+        let cfg = Cfg::new(&listing.instructions(), None);
+        assert!(cfg.flows.iter().all(|f| *f == Flow::Next));
+        assert_eq!(cfg.blocks.len(), 1);
+    }
+
+    #[test]
+    fn cfg_blocks() {
+        let listing = asm::parse(&std::fs::read_to_string("inputs/day19.txt").unwrap()).unwrap();
+        let cfg = Cfg::of(&listing);
+        let blocks: Vec<(usize, usize)> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(
+            blocks,
+            [
+                (0, 1),
+                (1, 2),
+                (2, 3),
+                (3, 6),
+                (6, 7),
+                (7, 8),
+                (8, 11),
+                (11, 12),
+                (12, 15),
+                (15, 16),
+                (16, 17),
+                (17, 26),
+                (26, 27),
+                (27, 36),
+            ]
+        );
+        assert_eq!(
+            cfg.block_of(4).unwrap().targets,
+            [Target::Ip(6), Target::Ip(7)]
+        );
+        assert_eq!(cfg.block_of(20).unwrap().targets, [Target::Unknown]);
+        assert_eq!(cfg.blocks[10].targets, [Target::Halt]);
+    }
+
+    #[test]
+    fn cfg_dot() {
+        let listing = asm::parse(
+            "#ip 3
+seti 0 0 2
+gtri 0 5 1
+addr 3 1 3
+addr 3 0 3
+seti 9 0 3
+",
+        )
+        .unwrap();
+        assert_eq!(
+            Cfg::of(&listing).to_dot(),
+            r#"digraph program {
+    node [shape=box, fontname=monospace];
+    b0 [label="ip 0-2\lseti 0 0 2\lgtri 0 5 1\laddr 3 1 3\l"];
+    b0 -> b3 [label="r1 == 0"];
+    b0 -> b4 [label="r1 == 1"];
+    b3 [label="ip 3\laddr 3 0 3\l"];
+    b3 -> unknown [style=dashed];
+    b4 [label="ip 4\lseti 9 0 3\l"];
+    b4 -> halt;
+    halt [shape=doublecircle];
+    unknown [shape=diamond, style=dashed, label="?"];
+}
+"#
+        );
+    }
+}
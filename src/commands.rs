@@ -0,0 +1,301 @@
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::opcodes::{Overflow, RunOptions, Vm};
+use crate::runner::{self, DayResult, InputSource};
+use crate::solution::{self, Day, Solution};
+use crate::{aoc16, asm, bench, cfg, debugger, decompile, report, trace, verify};
+
+// The subcommands of the binary. Each one prints its results to stdout and what went wrong to
+// stderr, and returns false if it failed so the binary can exit with an error.
+
+pub fn list() {
+    let days: Vec<String> = solution::registry()
+        .iter()
+        .map(|d| d.day.to_string())
+        .collect();
+    println!("Implemented days: {}", days.join(", "));
+}
+
+// Reads and parses a file, errors are printed
+fn read<T>(file: &str, parse: impl FnOnce(&str) -> Result<T>) -> Option<T> {
+    match fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| parse(&s).map_err(|e| e.to_string()))
+    {
+        Ok(v) => Some(v),
+        Err(e) => {
+            eprintln!("could not read {}: {}", file, e);
+            None
+        }
+    }
+}
+
+pub fn read_answers(answers_file: &str) -> Option<verify::Answers> {
+    read(answers_file, verify::Answers::parse)
+}
+
+fn read_program(program_file: &str) -> Option<asm::Listing> {
+    read(program_file, asm::parse)
+}
+
+fn load_program(program_file: &str) -> Option<Vm> {
+    read(program_file, |s| asm::parse(s)?.vm())
+}
+
+// Prints the answers of a day, fails if it could not be solved at all
+fn solve(day: &Day, source: &InputSource) -> Result<DayResult> {
+    let res = runner::solve_day(day, source).map_err(|e| {
+        eprintln!("error: {}", e);
+        e
+    })?;
+
+    for (part, answer) in [(1, &res.part_one), (2, &res.part_two)].iter() {
+        match answer {
+            Ok(_) => println!("{}:{} - {}", res.day, part, runner::display_answer(answer)),
+            Err(_) => eprintln!("{}:{} - {}", res.day, part, runner::display_answer(answer)),
+        }
+    }
+    Ok(res)
+}
+
+// Returns false unless both parts were answered
+pub fn solve_one(day: &Day, source: &InputSource) -> bool {
+    solve(day, source).is_ok_and(|res| res.part_one.is_ok() && res.part_two.is_ok())
+}
+
+// Prints the answers of every day and then a table of how long they took, returns false unless
+// every day was solved
+pub fn solve_all(days: &[Day], source: &InputSource) -> bool {
+    let results: Vec<DayResult> = days.iter().filter_map(|d| solve(d, source).ok()).collect();
+    println!();
+    runner::print_table(&results);
+    results.len() == days.len()
+        && results
+            .iter()
+            .all(|res| res.part_one.is_ok() && res.part_two.is_ok())
+}
+
+// Prints the records for each day as soon as it is done, returns false if any part failed or
+// does not match the answers file
+pub fn solve_json(days: &[Day], source: &InputSource, answers: Option<&verify::Answers>) -> bool {
+    let mut ok = true;
+    for day in days {
+        let res = runner::solve_day(day, source);
+        for r in report::records(day.day, &res, answers) {
+            ok &= !matches!(r.status, report::Status::Fail | report::Status::Error);
+            println!("{}", r.to_json());
+        }
+    }
+    ok
+}
+
+// Returns false if any answer does not match the answers file. Days without input are not
+// run and count as missing.
+pub fn verify(days: &[Day], source: &InputSource, answers: &verify::Answers) -> bool {
+    let (with_input, no_input): (Vec<&Day>, Vec<&Day>) =
+        days.iter().partition(|d| source.has_input(d));
+    let results: Vec<(usize, Result<DayResult>)> = with_input
+        .into_iter()
+        .map(|d| (d.day, solve(d, source)))
+        .collect();
+    let no_input: Vec<usize> = no_input.iter().map(|d| d.day).collect();
+    println!();
+    verify::report(answers, &results, &no_input) == 0
+}
+
+// Debugs an ElfCode program with commands read from stdin
+pub fn debug(program_file: &str, overflow: Overflow) -> bool {
+    let mut vm = match load_program(program_file) {
+        Some(vm) => vm,
+        None => return false,
+    };
+    vm.overflow = overflow;
+    let mut debugger = debugger::Debugger::new(vm);
+    let stdin = io::stdin();
+    match debugger.session(stdin.lock(), &mut io::stdout()) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            false
+        }
+    }
+}
+
+// Runs an ElfCode program until it halts or has run `steps` instructions, with r0 set to `r0`,
+// and prints how often each instruction ran. Returns false on a fault.
+pub fn profile(program_file: &str, steps: Option<usize>, r0: usize, overflow: Overflow) -> bool {
+    let mut vm = match load_program(program_file) {
+        Some(vm) => vm,
+        None => return false,
+    };
+    vm.regs[0] = r0;
+    vm.profile();
+    vm.overflow = overflow;
+    let res = match steps {
+        Some(steps) => vm.run_steps(steps),
+        None => vm.run(),
+    };
+    if let Err(fault) = &res {
+        eprintln!("{}", fault);
+    } else if !vm.halted() {
+        println!("stopped at ip {} before halting", vm.ip);
+    }
+    print!("{}", vm.profile.unwrap().report(&vm.program));
+    res.is_ok()
+}
+
+// Runs an ElfCode program with r0 set to `r0` until it halts, repeats a state, has run `steps`
+// instructions or runs out of time, and prints how it stopped. Returns false on a fault.
+pub fn run(
+    program_file: &str,
+    steps: Option<usize>,
+    r0: usize,
+    overflow: Overflow,
+    timeout: Option<Duration>,
+) -> bool {
+    let mut vm = match load_program(program_file) {
+        Some(vm) => vm,
+        None => return false,
+    };
+    vm.regs[0] = r0;
+    vm.overflow = overflow;
+    vm.optimise();
+    let options = RunOptions {
+        max_steps: steps,
+        timeout,
+        detect_loops: true,
+    };
+    let res = vm.run_with(&options);
+    match res {
+        Ok(outcome) => println!("{}", outcome),
+        Err(fault) => println!("{}", fault),
+    }
+    println!("ip {} {:?}", vm.ip, vm.regs);
+    res.is_ok()
+}
+
+// Runs an ElfCode program like `run` and prints a line for every executed instruction as it
+// runs. Stops after trace::DEFAULT_MAX_STEPS unless `steps` says otherwise. Anything else goes
+// to stderr so the output can be compared with `diff`.
+pub fn trace(
+    program_file: &str,
+    steps: Option<usize>,
+    r0: usize,
+    overflow: Overflow,
+    timeout: Option<Duration>,
+    optimise: bool,
+) -> bool {
+    let mut vm = match load_program(program_file) {
+        Some(vm) => vm,
+        None => return false,
+    };
+    vm.regs[0] = r0;
+    vm.overflow = overflow;
+    if optimise {
+        vm.optimise();
+    }
+    vm.trace_to(io::BufWriter::new(io::stdout()));
+    let options = RunOptions {
+        max_steps: Some(steps.unwrap_or(trace::DEFAULT_MAX_STEPS)),
+        timeout,
+        detect_loops: true,
+    };
+    let res = vm.run_with(&options);
+    if let Err(e) = vm.trace.take().unwrap().finish() {
+        eprintln!("could not write the trace: {}", e);
+        return false;
+    }
+    match res {
+        Ok(outcome) => eprintln!("{}", outcome),
+        Err(fault) => eprintln!("{}", fault),
+    }
+    res.is_ok()
+}
+
+// Prints the first steps where two traces differ, returns false if they do
+pub fn diff(left_file: &str, right_file: &str) -> bool {
+    let (left, right) = match (
+        read(left_file, trace::Trace::parse),
+        read(right_file, trace::Trace::parse),
+    ) {
+        (Some(left), Some(right)) => (left, right),
+        _ => return false,
+    };
+
+    match trace::diff(&left, &right) {
+        Some(divergence) => {
+            print!("{}", divergence);
+            false
+        }
+        None => {
+            println!("the traces agree");
+            true
+        }
+    }
+}
+
+// Runs an ElfCode program with each engine and prints how many instructions per second they
+// execute, overflows always wrap
+pub fn bench(program_file: &str, steps: Option<usize>, r0: usize) -> bool {
+    let listing = match read_program(program_file) {
+        Some(listing) => listing,
+        None => return false,
+    };
+
+    match bench::run(&listing, r0, steps.unwrap_or(usize::MAX)) {
+        Ok(measurements) => {
+            print!("{}", bench::report(&measurements));
+            true
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            false
+        }
+    }
+}
+
+pub fn decompile(program_file: &str) -> bool {
+    match read_program(program_file) {
+        Some(listing) => {
+            print!("{}", decompile::decompile(&listing));
+            true
+        }
+        None => false,
+    }
+}
+
+// Writes the control-flow graph of an ElfCode program to `output`, or prints it
+pub fn dot(program_file: &str, output: Option<&str>) -> bool {
+    let listing = match read_program(program_file) {
+        Some(listing) => listing,
+        None => return false,
+    };
+    let dot = cfg::Cfg::of(&listing).to_dot();
+    match output {
+        Some(output) => match fs::write(output, dot) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("could not write {}: {}", output, e);
+                false
+            }
+        },
+        None => {
+            print!("{}", dot);
+            true
+        }
+    }
+}
+
+// Prints how the samples in a day 16 input behave, see aoc16::Analysis::report
+pub fn samples(input_file: &str) -> bool {
+    match read(input_file, aoc16::Day16::parse) {
+        Some(program) => {
+            print!("{}", aoc16::analyse(&program).report());
+            true
+        }
+        None => false,
+    }
+}
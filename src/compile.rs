@@ -0,0 +1,241 @@
+use crate::opcodes::{Fault, FaultKind, Instruction, Opcode, Overflow, Vm, MAX_REGISTERS};
+
+// Register files are copied out of the VM while running, indexing the array with the operand
+// masked by `MASK` needs no bounds checks
+const MASK: usize = MAX_REGISTERS - 1;
+
+type File = [usize; MAX_REGISTERS];
+// Executes an instruction at the ip it is given, returns the next ip or None if it overflowed
+type Op = Box<dyn Fn(&mut File, usize) -> Option<usize> + Send + Sync>;
+
+// A program decoded once into a closure per instruction, with the operands, the operation and
+// the handling of the ip register baked in. Runs the same as Vm::step including the idioms,
+// but does not profile.
+pub struct Compiled {
+    ops: Vec<Op>,
+}
+
+// Only instructions that write the ip register need to read the ip back from it
+fn with_ip<F>(f: F, ip_reg: Option<usize>, c: usize) -> Op
+where
+    F: Fn(&mut File) -> bool + Send + Sync + 'static,
+{
+    match ip_reg.map(|r| r & MASK) {
+        None => Box::new(
+            move |r: &mut File, ip| {
+                if f(r) {
+                    Some(ip + 1)
+                } else {
+                    None
+                }
+            },
+        ),
+        Some(ip_reg) if ip_reg == c => Box::new(move |r: &mut File, ip| {
+            r[ip_reg] = ip;
+            if f(r) {
+                Some(r[ip_reg].saturating_add(1))
+            } else {
+                None
+            }
+        }),
+        Some(ip_reg) => Box::new(move |r: &mut File, ip| {
+            r[ip_reg] = ip;
+            if f(r) {
+                Some(ip + 1)
+            } else {
+                None
+            }
+        }),
+    }
+}
+
+// Writes an addition or multiplication, false if it overflowed and that is a fault
+fn store(r: &mut File, c: usize, (v, overflowed): (usize, bool), overflow: Overflow) -> bool {
+    match overflow.resolve(v, overflowed) {
+        Ok(v) => {
+            r[c] = v;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+// Writes the result of an operation that can not overflow
+fn set(r: &mut File, c: usize, v: usize) -> bool {
+    r[c] = v;
+    true
+}
+
+// The operands are known to name registers that exist
+fn compile(i: Instruction, ip_reg: Option<usize>, overflow: Overflow) -> Op {
+    let (a, b, c) = (i.a & MASK, i.b & MASK, i.c & MASK);
+    // Immediate values are not masked
+    let (ia, ib) = (i.a, i.b);
+    let o = overflow;
+    match i.op {
+        Opcode::Addr => with_ip(
+            move |r| store(r, c, r[a].overflowing_add(r[b]), o),
+            ip_reg,
+            c,
+        ),
+        Opcode::Addi => with_ip(move |r| store(r, c, r[a].overflowing_add(ib), o), ip_reg, c),
+        Opcode::Mulr => with_ip(
+            move |r| store(r, c, r[a].overflowing_mul(r[b]), o),
+            ip_reg,
+            c,
+        ),
+        Opcode::Muli => with_ip(move |r| store(r, c, r[a].overflowing_mul(ib), o), ip_reg, c),
+        Opcode::Banr => with_ip(move |r| set(r, c, r[a] & r[b]), ip_reg, c),
+        Opcode::Bani => with_ip(move |r| set(r, c, r[a] & ib), ip_reg, c),
+        Opcode::Borr => with_ip(move |r| set(r, c, r[a] | r[b]), ip_reg, c),
+        Opcode::Bori => with_ip(move |r| set(r, c, r[a] | ib), ip_reg, c),
+        Opcode::Setr => with_ip(move |r| set(r, c, r[a]), ip_reg, c),
+        Opcode::Seti => with_ip(move |r| set(r, c, ia), ip_reg, c),
+        Opcode::Gtir => with_ip(move |r| set(r, c, (ia > r[b]) as usize), ip_reg, c),
+        Opcode::Gtri => with_ip(move |r| set(r, c, (r[a] > ib) as usize), ip_reg, c),
+        Opcode::Gtrr => with_ip(move |r| set(r, c, (r[a] > r[b]) as usize), ip_reg, c),
+        Opcode::Eqir => with_ip(move |r| set(r, c, (ia == r[b]) as usize), ip_reg, c),
+        Opcode::Eqri => with_ip(move |r| set(r, c, (r[a] == ib) as usize), ip_reg, c),
+        Opcode::Eqrr => with_ip(move |r| set(r, c, (r[a] == r[b]) as usize), ip_reg, c),
+    }
+}
+
+impl Compiled {
+    // None if an instruction names a register that does not exist, Vm::step is the only way
+    // to run those
+    pub fn new(vm: &Vm) -> Option<Self> {
+        let num_regs = vm.regs.width();
+        if vm.ip_reg.is_some_and(|r| r >= num_regs)
+            || vm
+                .program
+                .iter()
+                .any(|i| i.invalid_register(num_regs).is_some())
+        {
+            return None;
+        }
+
+        let ops = vm
+            .program
+            .iter()
+            .enumerate()
+            .map(|(ip, i)| match vm.idioms.get(ip) {
+                // The registers are checked above, so the idiom can not fault. When it would
+                // overflow, its first instruction runs as usual instead.
+                Some(Some(idiom)) => {
+                    let idiom = *idiom;
+                    let op = compile(*i, vm.ip_reg, vm.overflow);
+                    Box::new(move |r: &mut File, ip| {
+                        match idiom.apply(&mut r[..num_regs]).ok().flatten() {
+                            Some(next) => Some(next),
+                            None => op(r, ip),
+                        }
+                    }) as Op
+                }
+                _ => compile(*i, vm.ip_reg, vm.overflow),
+            })
+            .collect();
+        Some(Compiled { ops })
+    }
+
+    // Runs at most `max` instructions on the registers and ip of `vm`, returns the number of
+    // executed instructions. Only overflows can fault, the registers are checked beforehand.
+    // Like Vm::step, the ip register holds the faulting ip after a fault.
+    pub fn run(&self, vm: &mut Vm, max: usize) -> Result<usize, Fault> {
+        let mut regs = *vm.regs.file();
+        let mut ip = vm.ip;
+        let mut steps = 0;
+        let mut overflowed = false;
+        while let Some(op) = self.ops.get(ip).filter(|_| steps < max) {
+            match op(&mut regs, ip) {
+                Some(next) => ip = next,
+                None => {
+                    overflowed = true;
+                    break;
+                }
+            }
+            steps += 1;
+        }
+        *vm.regs.file_mut() = regs;
+        vm.ip = ip;
+        if overflowed {
+            return Err(Fault {
+                ip,
+                instruction: vm.program[ip],
+                kind: FaultKind::Overflow,
+            });
+        }
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn compile_run() {
+        let listing = asm::parse(&std::fs::read_to_string("inputs/day19.txt").unwrap()).unwrap();
+        let mut vm = listing.vm().unwrap();
+        let mut compiled = vm.clone();
+        assert_eq!(
+            Compiled::new(&compiled)
+                .unwrap()
+                .run(&mut compiled, 100_000),
+            vm.run_steps(100_000)
+        );
+        assert_eq!((compiled.regs, compiled.ip), (vm.regs, vm.ip));
+
+        let mut vm = listing.vm().unwrap();
+        vm.optimise();
+        let steps = vm.clone().run();
+        assert_eq!(vm.run_compiled(), steps);
+        assert!(steps.is_ok());
+        assert_eq!(vm.regs[0], 1464);
+        assert!(vm.halted());
+
+        // Every operation, without a bound ip
+        let listing = asm::parse(
+            "seti 7 0 0\naddi 0 3 1\naddr 0 1 2\nmuli 2 2 3\nmulr 3 1 2\nbani 2 6 0\nbanr 0 2 1\n\
+             bori 1 8 2\nborr 2 0 3\nsetr 3 0 0\ngtir 5 0 1\ngtri 0 5 2\ngtrr 2 1 3\neqir 14 0 1\n\
+             eqri 1 0 2\neqrr 1 2 3\n",
+        )
+        .unwrap();
+        let mut vm = listing.vm().unwrap();
+        let mut compiled = vm.clone();
+        vm.run().unwrap();
+        assert_eq!(compiled.run_compiled(), Ok(16));
+        assert_eq!(compiled.regs, vm.regs);
+
+        // Immediate values can be anything, registers have to exist
+        let vm = asm::parse("#ip 4\nseti 100 0 0\naddi 0 9 5\n")
+            .unwrap()
+            .vm()
+            .unwrap();
+        assert!(Compiled::new(&vm).is_some());
+        let mut vm = Vm::new(Vec::new(), None, 6).unwrap();
+        vm.program = vec![Instruction::new(Opcode::Addr, 0, 6, 5)];
+        assert!(Compiled::new(&vm).is_none());
+    }
+
+    #[test]
+    fn compile_overflow() {
+        // Doubles r1 until it overflows
+        let listing = asm::parse("#ip 0\nseti 1 0 1\nmuli 1 2 1\nseti 0 0 0\n").unwrap();
+        for overflow in [Overflow::Wrap, Overflow::Saturate, Overflow::Error].iter() {
+            let mut vm = listing.vm().unwrap();
+            vm.overflow = *overflow;
+            let mut compiled = vm.clone();
+            assert_eq!(
+                Compiled::new(&compiled).unwrap().run(&mut compiled, 200),
+                vm.run_steps(200)
+            );
+            assert_eq!((&compiled.regs, compiled.ip), (&vm.regs, vm.ip));
+        }
+
+        let mut vm = listing.vm().unwrap();
+        let fault = vm.run_compiled().unwrap_err();
+        assert_eq!((fault.ip, fault.kind), (1, FaultKind::Overflow));
+        assert_eq!(vm.regs[1], 1 << (usize::BITS - 1));
+    }
+}
@@ -0,0 +1,398 @@
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+use crate::error::{Error, Result};
+use crate::opcodes::{Fault, Vm};
+
+// A line based debugger for the VM, so that sessions can be scripted:
+//
+// break 3            stop before the instruction at ip 3 runs
+// break r1 > 10      stop when a register condition holds
+// watch r0           stop after r0 changes
+// delete 1           remove breakpoint 1, `delete w1` removes watchpoint 1
+// step [n]           run one or n instructions
+// continue           run until a breakpoint, watchpoint or halt
+// regs               show the ip and registers
+// set r3 33          change a register, `set ip 5` moves the ip
+// list               show the program with the current ip marked
+// quit
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Cmp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "==" => Some(Cmp::Eq),
+            "!=" => Some(Cmp::Ne),
+            "<" => Some(Cmp::Lt),
+            "<=" => Some(Cmp::Le),
+            ">" => Some(Cmp::Gt),
+            ">=" => Some(Cmp::Ge),
+            _ => None,
+        }
+    }
+
+    fn holds(self, a: usize, b: usize) -> bool {
+        match self {
+            Cmp::Eq => a == b,
+            Cmp::Ne => a != b,
+            Cmp::Lt => a < b,
+            Cmp::Le => a <= b,
+            Cmp::Gt => a > b,
+            Cmp::Ge => a >= b,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Cmp::Eq => "==",
+            Cmp::Ne => "!=",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Breakpoint {
+    Ip(usize),
+    Reg(usize, Cmp, usize),
+}
+
+impl Breakpoint {
+    fn hit(&self, vm: &Vm) -> bool {
+        match *self {
+            Breakpoint::Ip(ip) => vm.ip == ip,
+            Breakpoint::Reg(r, cmp, v) => cmp.holds(vm.regs[r], v),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Ip(ip) => write!(f, "ip {}", ip),
+            Breakpoint::Reg(r, cmp, v) => write!(f, "r{} {} {}", r, cmp.symbol(), v),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Break(Breakpoint),
+    Watch(usize),
+    Delete(usize),
+    DeleteWatch(usize),
+    Step(usize),
+    Continue,
+    Regs,
+    SetReg(usize, usize),
+    SetIp(usize),
+    List,
+    Quit,
+}
+
+fn number(s: &str) -> Result<usize> {
+    s.parse()
+        .map_err(|_| Error::new(format!("expected a number, found '{}'", s)))
+}
+
+fn register(s: &str, registers: usize) -> Result<usize> {
+    let r = s
+        .strip_prefix('r')
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| Error::new(format!("expected a register like r0, found '{}'", s)))?;
+    if r >= registers {
+        return Err(Error::new(format!(
+            "there is no register {}, the registers are r0 to r{}",
+            s,
+            registers - 1
+        )));
+    }
+    Ok(r)
+}
+
+fn parse_command(line: &str, registers: usize) -> Result<Command> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let cmd = match words.as_slice() {
+        ["break", ip] | ["b", ip] => Command::Break(Breakpoint::Ip(number(ip)?)),
+        ["break", r, cmp, v] | ["b", r, cmp, v] => {
+            let cmp = Cmp::parse(cmp)
+                .ok_or_else(|| Error::new(format!("unknown comparison '{}'", cmp)))?;
+            Command::Break(Breakpoint::Reg(register(r, registers)?, cmp, number(v)?))
+        }
+        ["watch", r] | ["w", r] => Command::Watch(register(r, registers)?),
+        ["delete", n] | ["d", n] => match n.strip_prefix('w') {
+            Some(n) => Command::DeleteWatch(number(n)?),
+            None => Command::Delete(number(n)?),
+        },
+        ["step"] | ["s"] => Command::Step(1),
+        ["step", n] | ["s", n] => Command::Step(number(n)?),
+        ["continue"] | ["c"] => Command::Continue,
+        ["regs"] | ["r"] => Command::Regs,
+        ["set", "ip", v] => Command::SetIp(number(v)?),
+        ["set", r, v] => Command::SetReg(register(r, registers)?, number(v)?),
+        ["list"] | ["l"] => Command::List,
+        ["quit"] | ["q"] => Command::Quit,
+        _ => return Err(Error::new(format!("unknown command '{}'", line.trim()))),
+    };
+    Ok(cmd)
+}
+
+#[derive(Debug, PartialEq)]
+enum Stop {
+    Breakpoint(usize),
+    // Watchpoint, ip of the instruction that wrote the register, old and new value
+    Watch(usize, usize, usize, usize),
+    // The ip stays at the instruction, which runs again after fixing the registers
+    Fault(Fault),
+    Halted,
+    Done,
+}
+
+pub struct Debugger {
+    pub vm: Vm,
+    pub steps: usize,
+    // Deleted entries are kept as None so the numbers stay the same
+    breakpoints: Vec<Option<Breakpoint>>,
+    watches: Vec<Option<usize>>,
+}
+
+impl Debugger {
+    pub fn new(vm: Vm) -> Self {
+        Debugger {
+            vm,
+            steps: 0,
+            breakpoints: Vec::new(),
+            watches: Vec::new(),
+        }
+    }
+
+    fn location(&self) -> String {
+        match self.vm.program.get(self.vm.ip) {
+            Some(i) => format!("ip {}: {}", self.vm.ip, i),
+            None => format!("ip {}: halted", self.vm.ip),
+        }
+    }
+
+    // Runs up to `max` instructions, stopping early at breakpoints and watchpoints. The first
+    // instruction always runs, so that continuing from a breakpoint makes progress.
+    fn run(&mut self, max: Option<usize>) -> Stop {
+        let mut n = 0;
+        loop {
+            if max == Some(n) {
+                return Stop::Done;
+            }
+            if self.vm.halted() {
+                return Stop::Halted;
+            }
+            if n > 0 {
+                let hit = self
+                    .breakpoints
+                    .iter()
+                    .position(|b| b.is_some_and(|b| b.hit(&self.vm)));
+                if let Some(b) = hit {
+                    return Stop::Breakpoint(b + 1);
+                }
+            }
+
+            let ip = self.vm.ip;
+            let before = self.vm.regs.clone();
+            if let Err(fault) = self.vm.step() {
+                return Stop::Fault(fault);
+            }
+            self.steps += 1;
+            n += 1;
+
+            for (i, w) in self.watches.iter().enumerate() {
+                if let Some(r) = *w {
+                    if self.vm.regs[r] != before[r] {
+                        return Stop::Watch(i + 1, ip, before[r], self.vm.regs[r]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn report(&self, stop: Stop, out: &mut impl Write) -> io::Result<()> {
+        match stop {
+            Stop::Breakpoint(b) => writeln!(
+                out,
+                "breakpoint {} ({}) at {}",
+                b,
+                self.breakpoints[b - 1].unwrap(),
+                self.location()
+            ),
+            Stop::Watch(w, ip, old, new) => writeln!(
+                out,
+                "watchpoint {}: r{} {} -> {} by ip {}: {}",
+                w,
+                self.watches[w - 1].unwrap(),
+                old,
+                new,
+                ip,
+                self.vm.program[ip]
+            ),
+            Stop::Fault(fault) => writeln!(out, "{}", fault),
+            Stop::Halted => writeln!(out, "halted after {} steps", self.steps),
+            Stop::Done => writeln!(out, "{}", self.location()),
+        }
+    }
+
+    // Returns false when the session should end
+    fn execute(&mut self, cmd: Command, out: &mut impl Write) -> io::Result<bool> {
+        match cmd {
+            Command::Break(b) => {
+                self.breakpoints.push(Some(b));
+                writeln!(out, "breakpoint {} at {}", self.breakpoints.len(), b)?;
+            }
+            Command::Watch(r) => {
+                self.watches.push(Some(r));
+                writeln!(out, "watchpoint {} on r{}", self.watches.len(), r)?;
+            }
+            Command::Delete(n) => match self.breakpoints.get_mut(n.wrapping_sub(1)) {
+                Some(b @ Some(_)) => *b = None,
+                _ => writeln!(out, "error: there is no breakpoint {}", n)?,
+            },
+            Command::DeleteWatch(n) => match self.watches.get_mut(n.wrapping_sub(1)) {
+                Some(w @ Some(_)) => *w = None,
+                _ => writeln!(out, "error: there is no watchpoint w{}", n)?,
+            },
+            Command::Step(n) => {
+                let stop = self.run(Some(n));
+                self.report(stop, out)?;
+            }
+            Command::Continue => {
+                let stop = self.run(None);
+                self.report(stop, out)?;
+            }
+            Command::Regs => writeln!(out, "ip {} {:?}", self.vm.ip, self.vm.regs)?,
+            Command::SetReg(r, v) => self.vm.regs[r] = v,
+            Command::SetIp(ip) => self.vm.ip = ip,
+            Command::List => {
+                for (ip, i) in self.vm.program.iter().enumerate() {
+                    let mark = if ip == self.vm.ip { '>' } else { ' ' };
+                    writeln!(out, "{} {:>3}  {}", mark, ip, i)?;
+                }
+            }
+            Command::Quit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Reads commands until `quit` or the end of the input, blank lines and lines starting with
+    // `#` are skipped. Bad commands are reported and the session goes on.
+    pub fn session(&mut self, input: impl BufRead, out: &mut impl Write) -> io::Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            match parse_command(&line, self.vm.regs.len()) {
+                Ok(cmd) => {
+                    if !self.execute(cmd, out)? {
+                        break;
+                    }
+                }
+                Err(e) => writeln!(out, "error: {}", e)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    const EXAMPLE: &str = "#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5
+";
+
+    fn session(script: &str) -> String {
+        let mut debugger = Debugger::new(asm::parse(EXAMPLE).unwrap().vm().unwrap());
+        let mut out = Vec::new();
+        debugger.session(script.as_bytes(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn debugger_parse() {
+        assert_eq!(
+            parse_command("break r2 >= 6", 6),
+            Ok(Command::Break(Breakpoint::Reg(2, Cmp::Ge, 6)))
+        );
+        assert_eq!(parse_command("  s 3 ", 6), Ok(Command::Step(3)));
+        assert_eq!(parse_command("d w2", 6), Ok(Command::DeleteWatch(2)));
+        assert_eq!(parse_command("set ip 4", 6), Ok(Command::SetIp(4)));
+        assert!(parse_command("watch r6", 6).is_err());
+        assert!(parse_command("break r1 =< 2", 6).is_err());
+        assert!(parse_command("jump 2", 6).is_err());
+    }
+
+    #[test]
+    fn debugger_session() {
+        let out = session(
+            "# comment
+break 4
+step
+regs
+continue
+watch r0
+continue
+delete w1
+continue
+",
+        );
+        assert_eq!(
+            out,
+            "breakpoint 1 at ip 4
+ip 1: seti 6 0 2
+ip 1 [0, 5, 0, 0, 0, 0]
+breakpoint 1 (ip 4) at ip 4: setr 1 0 0
+watchpoint 1 on r0
+watchpoint 1: r0 3 -> 5 by ip 4: setr 1 0 0
+halted after 5 steps
+"
+        );
+
+        let out = session("break r2 == 6\nc\nset r1 0\nset ip 6\ns 2\nregs\nbogus\nquit\nregs\n");
+        assert_eq!(
+            out,
+            "breakpoint 1 at r2 == 6
+breakpoint 1 (r2 == 6) at ip 2: addi 0 1 0
+halted after 3 steps
+ip 7 [6, 0, 6, 0, 0, 9]
+error: unknown command 'bogus'
+"
+        );
+
+        // A fault stops at the instruction, which runs again on continuing
+        let out =
+            session("set r1 18446744073709551615\nset r2 1\nset ip 3\nc\nset r2 0\nc\nregs\n");
+        assert_eq!(
+            out,
+            "fault at ip 3 'addr 1 2 3': overflow
+halted after 2 steps
+ip 18446744073709551615 [18446744073709551615, 18446744073709551615, 0, 18446744073709551615, 0, 0]
+"
+        );
+    }
+}
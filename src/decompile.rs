@@ -0,0 +1,527 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::asm::Listing;
+use crate::cfg::{Cfg, Flow, Target};
+use crate::opcodes::Opcode;
+
+// Turns a program into pseudocode. Jumps through the bound ip register become gotos, and
+// gotos that form single entry loops and if/else blocks become structured statements:
+//
+//     f = 1
+//     do {
+//         b = e * f
+//         if b == d {
+//             a += e
+//         }
+//         f += 1
+//     } while f <= d
+//
+// Registers are named a, b, c, ... and reads of the ip register are replaced by the ip of
+// the instruction, jumps that can not be resolved statically are kept as computed gotos.
+
+#[derive(Debug, Clone, PartialEq)]
+struct Cond {
+    lhs: String,
+    op: &'static str,
+    rhs: String,
+}
+
+impl Cond {
+    fn negate(&self) -> Self {
+        let op = match self.op {
+            "==" => "!=",
+            "!=" => "==",
+            ">" => "<=",
+            "<=" => ">",
+            _ => unreachable!("no other comparisons are created"),
+        };
+        Cond { op, ..self.clone() }
+    }
+}
+
+impl fmt::Display for Cond {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.lhs, self.op, self.rhs)
+    }
+}
+
+// One per instruction
+#[derive(Debug, Clone, PartialEq)]
+enum Stmt {
+    Assign(String),
+    // A comparison that is only used by the branch after it
+    Hidden,
+    // goto target if the condition holds
+    Branch(Cond, usize),
+    Goto(usize),
+    Computed(String),
+}
+
+// Every node starts at an ip, where a label can go
+#[derive(Debug, PartialEq)]
+enum Node {
+    Stmt(usize),
+    If(usize, Cond, Vec<Node>, Vec<Node>),
+    // An endless loop, or a do-while loop with the condition
+    Loop(usize, Vec<Node>, Option<Cond>),
+    Break(usize),
+    Continue(usize),
+}
+
+impl Node {
+    fn ip(&self) -> usize {
+        match self {
+            Node::Stmt(ip)
+            | Node::If(ip, ..)
+            | Node::Loop(ip, ..)
+            | Node::Break(ip)
+            | Node::Continue(ip) => *ip,
+        }
+    }
+}
+
+pub fn register_name(r: usize) -> String {
+    if r < 26 {
+        ((b'a' + r as u8) as char).to_string()
+    } else {
+        format!("r{}", r)
+    }
+}
+
+fn operator(op: Opcode) -> &'static str {
+    match op {
+        Opcode::Addr | Opcode::Addi => "+",
+        Opcode::Mulr | Opcode::Muli => "*",
+        Opcode::Banr | Opcode::Bani => "&",
+        Opcode::Borr | Opcode::Bori => "|",
+        Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => ">",
+        Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => "==",
+        Opcode::Setr | Opcode::Seti => "",
+    }
+}
+
+struct Decompiler {
+    cfg: Cfg,
+    stmts: Vec<Stmt>,
+}
+
+impl Decompiler {
+    fn new(cfg: Cfg) -> Self {
+        let mut d = Decompiler {
+            cfg,
+            stmts: Vec::new(),
+        };
+        for ip in 0..d.cfg.program.len() {
+            let stmt = d.stmt(ip);
+            d.stmts.push(stmt);
+        }
+        d
+    }
+
+    fn len(&self) -> usize {
+        self.cfg.program.len()
+    }
+
+    fn name(&self, r: usize) -> String {
+        if Some(r) == self.cfg.ip_reg {
+            "ip".to_owned()
+        } else {
+            register_name(r)
+        }
+    }
+
+    fn operand(&self, ip: usize, is_reg: bool, v: usize) -> String {
+        if !is_reg {
+            v.to_string()
+        } else if Some(v) == self.cfg.ip_reg {
+            ip.to_string()
+        } else {
+            self.name(v)
+        }
+    }
+
+    // The value the instruction at `ip` computes
+    fn expression(&self, ip: usize) -> String {
+        let i = self.cfg.program[ip];
+        let a = self.operand(ip, i.op.reads_a(), i.a);
+        let b = self.operand(ip, i.op.reads_b(), i.b);
+        match i.op {
+            Opcode::Setr | Opcode::Seti => a,
+            op => format!("{} {} {}", a, operator(op), b),
+        }
+    }
+
+    fn assignment(&self, ip: usize) -> String {
+        let i = self.cfg.program[ip];
+        let c = self.name(i.c);
+        let not_ip = |r| Some(r) != self.cfg.ip_reg;
+        let op = i.op;
+        if op == Opcode::Setr || op == Opcode::Seti || op.is_comparison() {
+            format!("{} = {}", c, self.expression(ip))
+        } else if op.reads_a() && i.a == i.c && not_ip(i.a) {
+            let b = self.operand(ip, op.reads_b(), i.b);
+            format!("{} {}= {}", c, operator(op), b)
+        } else if op.reads_b() && i.b == i.c && not_ip(i.b) {
+            let a = self.operand(ip, op.reads_a(), i.a);
+            format!("{} {}= {}", c, operator(op), a)
+        } else {
+            format!("{} = {}", c, self.expression(ip))
+        }
+    }
+
+    // Whether `reg` can be read before it is written when continuing at `from`
+    fn live(&self, reg: usize, from: &[usize]) -> bool {
+        let mut stack = from.to_vec();
+        let mut seen = BTreeSet::new();
+        while let Some(ip) = stack.pop() {
+            if ip >= self.len() || !seen.insert(ip) {
+                continue;
+            }
+            let i = self.cfg.program[ip];
+            if i.reads().any(|r| r == reg) {
+                return true;
+            }
+            if i.c == reg {
+                continue;
+            }
+            for t in self.cfg.targets(ip) {
+                match t {
+                    Target::Ip(t) => stack.push(t),
+                    Target::Halt => (),
+                    Target::Unknown => return true,
+                }
+            }
+        }
+        false
+    }
+
+    fn stmt(&mut self, ip: usize) -> Stmt {
+        match self.cfg.flows[ip] {
+            Flow::Next => Stmt::Assign(self.assignment(ip)),
+            Flow::Jump(t) => Stmt::Goto(t),
+            Flow::Skip(r) => {
+                let prev = self.cfg.program[ip - 1];
+                let live = self.live(r, &[ip + 1, ip + 2]);
+                if !live {
+                    self.stmts[ip - 1] = Stmt::Hidden;
+                }
+                // Once the comparison has overwritten one of its operands only r is left
+                let cond = if live && prev.reads().any(|r| r == prev.c) {
+                    Cond {
+                        lhs: self.name(r),
+                        op: "!=",
+                        rhs: "0".to_owned(),
+                    }
+                } else {
+                    Cond {
+                        lhs: self.operand(ip - 1, prev.op.reads_a(), prev.a),
+                        op: operator(prev.op),
+                        rhs: self.operand(ip - 1, prev.op.reads_b(), prev.b),
+                    }
+                };
+                Stmt::Branch(cond, ip + 2)
+            }
+            Flow::Computed => {
+                let i = self.cfg.program[ip];
+                let expr = match (i.op, self.cfg.ip_reg) {
+                    // Fold the constant ip into the jump offset
+                    (Opcode::Addr, Some(r)) if i.a == r && i.b != r => {
+                        format!("{} + {}", ip + 1, self.name(i.b))
+                    }
+                    (Opcode::Addr, Some(r)) if i.b == r && i.a != r => {
+                        format!("{} + {}", ip + 1, self.name(i.a))
+                    }
+                    _ => format!("({}) + 1", self.expression(ip)),
+                };
+                Stmt::Computed(format!("goto {}", expr))
+            }
+        }
+    }
+
+    fn successors(&self, ip: usize) -> impl Iterator<Item = usize> + '_ {
+        self.cfg.targets(ip).into_iter().filter_map(|t| match t {
+            Target::Ip(t) => Some(t),
+            _ => None,
+        })
+    }
+
+    // Edges into lo..=hi from outside of it, as (from, to)
+    fn entries(&self, lo: usize, hi: usize) -> Vec<(usize, usize)> {
+        (0..self.len())
+            .filter(|ip| *ip < lo || *ip > hi)
+            .flat_map(|ip| {
+                self.successors(ip)
+                    .filter(|t| lo <= *t && *t <= hi)
+                    .map(move |t| (ip, t))
+            })
+            .collect()
+    }
+
+    fn back_edge(&self, q: usize, header: usize) -> bool {
+        match self.stmts[q] {
+            Stmt::Goto(t) | Stmt::Branch(_, t) => t == header,
+            _ => false,
+        }
+    }
+
+    // Structures the statements lo..hi, `lp` is the header and exit of the enclosing loop
+    fn structure(&self, lo: usize, hi: usize, lp: Option<(usize, usize)>) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        let mut p = lo;
+        while p < hi {
+            let in_own_loop = p == lo && lp.map(|(h, _)| h) == Some(p);
+            if !in_own_loop {
+                let closing = (p..hi).rev().find(|q| self.back_edge(*q, p));
+                if let Some(q) = closing {
+                    if self.entries(p, q).iter().all(|(_, t)| *t == p) {
+                        let mut body = self.structure(p, q, Some((p, q + 1)));
+                        let cond = match &self.stmts[q] {
+                            Stmt::Branch(c, _) => Some(c.clone()),
+                            _ => match body.last() {
+                                Some(Node::If(_, c, then, els))
+                                    if then.len() == 1
+                                        && matches!(then[0], Node::Break(_))
+                                        && els.is_empty() =>
+                                {
+                                    let c = c.negate();
+                                    body.pop();
+                                    Some(c)
+                                }
+                                _ => None,
+                            },
+                        };
+                        nodes.push(Node::Loop(p, body, cond));
+                        p = q + 1;
+                        continue;
+                    }
+                }
+            }
+
+            let exit = match &self.stmts[p] {
+                Stmt::Goto(t) | Stmt::Branch(_, t) => match lp {
+                    Some((h, _)) if *t == h => Some(Node::Continue(p)),
+                    Some((_, e)) if *t == e => Some(Node::Break(p)),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match (&self.stmts[p], exit) {
+                (Stmt::Branch(c, _), Some(exit)) => {
+                    nodes.push(Node::If(p, c.clone(), vec![exit], vec![]));
+                }
+                (_, Some(exit)) => nodes.push(exit),
+                (Stmt::Branch(c, t), None)
+                    if p + 1 < *t && *t <= hi && self.only_from(p + 1, *t - 1, p) =>
+                {
+                    let t = *t;
+                    match self.stmts[t - 1] {
+                        // if the condition does not hold the else block is jumped over
+                        Stmt::Goto(u) if t < u && u <= hi && self.only_from(t, u - 1, p) => {
+                            let then = self.structure(p + 1, t - 1, lp);
+                            let els = self.structure(t, u, lp);
+                            if then.is_empty() {
+                                nodes.push(Node::If(p, c.clone(), els, vec![]));
+                            } else {
+                                nodes.push(Node::If(p, c.negate(), then, els));
+                            }
+                            p = u;
+                        }
+                        _ => {
+                            let then = self.structure(p + 1, t, lp);
+                            nodes.push(Node::If(p, c.negate(), then, vec![]));
+                            p = t;
+                        }
+                    }
+                    continue;
+                }
+                _ => nodes.push(Node::Stmt(p)),
+            }
+            p += 1;
+        }
+        nodes
+    }
+
+    // Whether lo..=hi can only be entered from `from`
+    fn only_from(&self, lo: usize, hi: usize, from: usize) -> bool {
+        self.entries(lo, hi).iter().all(|(f, _)| *f == from)
+    }
+
+    fn goto(&self, t: usize) -> String {
+        if t < self.len() {
+            format!("goto L{}", t)
+        } else {
+            "halt".to_owned()
+        }
+    }
+
+    // The targets of the gotos that are left
+    fn labels(&self, nodes: &[Node], labels: &mut BTreeSet<usize>) {
+        for node in nodes {
+            match node {
+                Node::Stmt(ip) => match self.stmts[*ip] {
+                    Stmt::Goto(t) | Stmt::Branch(_, t) if t < self.len() => {
+                        labels.insert(t);
+                    }
+                    _ => (),
+                },
+                Node::If(_, _, then, els) => {
+                    self.labels(then, labels);
+                    self.labels(els, labels);
+                }
+                Node::Loop(_, body, _) => self.labels(body, labels),
+                Node::Break(_) | Node::Continue(_) => (),
+            }
+        }
+    }
+
+    fn render(&self, nodes: &[Node], depth: usize, labels: &BTreeSet<usize>, out: &mut String) {
+        let indent = "    ".repeat(depth);
+        for node in nodes {
+            if labels.contains(&node.ip()) {
+                out.push_str(&format!("L{}:\n", node.ip()));
+            }
+            let line = |out: &mut String, s: &str| out.push_str(&format!("{}{}\n", indent, s));
+            match node {
+                Node::Stmt(ip) => match &self.stmts[*ip] {
+                    Stmt::Assign(s) | Stmt::Computed(s) => line(out, s),
+                    Stmt::Hidden => (),
+                    Stmt::Branch(c, t) => line(out, &format!("if {} {}", c, self.goto(*t))),
+                    Stmt::Goto(t) => line(out, &self.goto(*t)),
+                },
+                Node::If(_, c, then, els) => {
+                    line(out, &format!("if {} {{", c));
+                    self.render(then, depth + 1, labels, out);
+                    if !els.is_empty() {
+                        line(out, "} else {");
+                        self.render(els, depth + 1, labels, out);
+                    }
+                    line(out, "}");
+                }
+                Node::Loop(_, body, cond) => {
+                    line(out, if cond.is_some() { "do {" } else { "loop {" });
+                    self.render(body, depth + 1, labels, out);
+                    match cond {
+                        Some(c) => line(out, &format!("}} while {}", c)),
+                        None => line(out, "}"),
+                    }
+                }
+                Node::Break(_) => line(out, "break"),
+                Node::Continue(_) => line(out, "continue"),
+            }
+        }
+    }
+}
+
+pub fn decompile(listing: &Listing) -> String {
+    let d = Decompiler::new(Cfg::of(listing));
+    let nodes = d.structure(0, d.len(), None);
+    let mut labels = BTreeSet::new();
+    d.labels(&nodes, &mut labels);
+    let mut out = String::new();
+    d.render(&nodes, 1, &labels, &mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    fn decompiled(program: &str) -> String {
+        decompile(&asm::parse(program).unwrap())
+    }
+
+    #[test]
+    fn decompile_structures() {
+        // The comparison is still needed after the branch, so it stays
+        let if_else = "#ip 4
+seti 3 0 1
+gtri 1 2 2
+addr 4 2 4
+seti 5 0 4
+addi 0 1 0
+mulr 0 0 0
+addr 2 0 3
+mulr 4 0 4
+";
+        assert_eq!(
+            decompiled(if_else),
+            "    b = 3
+    c = b > 2
+    if b > 2 {
+        a += 1
+        a *= a
+    }
+    d = c + a
+    goto (7 * a) + 1
+"
+        );
+
+        let do_while = "#ip 3
+seti 0 0 2
+addi 0 1 0
+eqri 0 5 1
+addr 1 3 3
+seti 0 0 3
+";
+        assert_eq!(
+            decompiled(do_while),
+            "    c = 0
+    do {
+        a += 1
+    } while a != 5
+"
+        );
+
+        // Without a bound ip there is no control flow
+        assert_eq!(
+            decompiled("seti 1 0 0\ngtrr 0 1 2\n"),
+            "    a = 1\n    c = a > b\n"
+        );
+    }
+
+    #[test]
+    fn decompile_day19() {
+        let listing = asm::parse(&std::fs::read_to_string("inputs/day19.txt").unwrap()).unwrap();
+        assert_eq!(
+            decompile(&listing),
+            "    goto L17
+L1:
+    e = 1
+    do {
+        f = 1
+        do {
+            b = e * f
+            if b == d {
+                a += e
+            }
+            f += 1
+        } while f <= d
+        e += 1
+    } while e <= d
+    halt
+L17:
+    d += 2
+    d *= d
+    d *= 19
+    d *= 11
+    b += 6
+    b *= 22
+    b += 6
+    d += b
+    goto 26 + a
+    goto L1
+    b = 27
+    b *= 28
+    b += 29
+    b *= 30
+    b *= 14
+    b *= 32
+    d += b
+    a = 0
+    goto L1
+"
+        );
+    }
+}
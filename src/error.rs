@@ -0,0 +1,177 @@
+use std::fmt;
+
+use nom::IResult;
+
+use crate::opcodes::{Fault, LoadError};
+
+// An error with as much position information as is known, lines and columns start at 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub day: Option<usize>,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn new(message: impl Into<String>) -> Self {
+        Error {
+            day: None,
+            line: None,
+            column: None,
+            message: message.into(),
+        }
+    }
+
+    // Error at the start of `rest`, which must be a suffix of `input`
+    pub fn at(input: &str, rest: &str, message: impl Into<String>) -> Self {
+        let offset = input.len() - rest.len();
+        let consumed = &input[..offset];
+        let line = consumed.matches('\n').count() + 1;
+        let line_start = consumed.rfind('\n').map_or(0, |i| i + 1);
+        let column = consumed[line_start..].chars().count() + 1;
+        Error {
+            line: Some(line),
+            column: Some(column),
+            ..Error::new(message)
+        }
+    }
+
+    pub fn at_line(line: usize, message: impl Into<String>) -> Self {
+        Error {
+            line: Some(line),
+            ..Error::new(message)
+        }
+    }
+
+    pub fn with_day(mut self, day: usize) -> Self {
+        self.day = Some(day);
+        self
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(day) = self.day {
+            write!(f, "day {}, ", day)?;
+        }
+        if let Some(line) = self.line {
+            write!(f, "line {}, ", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, "column {}, ", column)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Fault> for Error {
+    fn from(fault: Fault) -> Self {
+        Error::new(fault.to_string())
+    }
+}
+
+impl From<LoadError> for Error {
+    fn from(e: LoadError) -> Self {
+        Error::new(e.to_string())
+    }
+}
+
+fn unexpected(rest: &str) -> String {
+    match rest.lines().next() {
+        Some(l) if !l.trim().is_empty() => format!("could not parse '{}'", l.trim_end()),
+        _ => "unexpected end of input".to_owned(),
+    }
+}
+
+// Converts the result of a nom parser run on `input`, any unparsed input is returned
+pub fn finish<'a, T>(input: &'a str, res: IResult<&'a str, T>) -> Result<(&'a str, T)> {
+    match res {
+        Ok(r) => Ok(r),
+        Err(nom::Err::Error((rest, _))) | Err(nom::Err::Failure((rest, _))) => {
+            Err(Error::at(input, rest, unexpected(rest)))
+        }
+        Err(nom::Err::Incomplete(_)) => Err(Error::new("unexpected end of input")),
+    }
+}
+
+// Like finish, but everything except trailing whitespace must be parsed
+pub fn parse_all<'a, T>(input: &'a str, res: IResult<&'a str, T>) -> Result<T> {
+    let (rest, v) = finish(input, res)?;
+    if rest.trim().is_empty() {
+        Ok(v)
+    } else {
+        Err(Error::at(input, rest, unexpected(rest)))
+    }
+}
+
+// Parses every line with `f`, errors are tagged with the line number. A line reported by `f`
+// is taken to be relative to the start of the line it was given.
+pub fn parse_lines<T>(input: &str, f: impl Fn(&str) -> Result<T>) -> Result<Vec<T>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(i, l)| {
+            f(l).map_err(|e| Error {
+                line: Some(i + e.line.unwrap_or(1)),
+                ..e
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::u32_val;
+    use nom::bytes::complete::tag;
+    use nom::multi::many1;
+    use nom::sequence::terminated;
+
+    fn numbers(i: &str) -> IResult<&str, Vec<u32>> {
+        many1(terminated(u32_val, tag("\n")))(i)
+    }
+
+    #[test]
+    fn error_position() {
+        let input = "abc\ndef\nghi";
+        assert_eq!(Error::at(input, input, "x").line, Some(1));
+        assert_eq!(Error::at(input, input, "x").column, Some(1));
+        let e = Error::at(input, &input[5..], "x");
+        assert_eq!((e.line, e.column), (Some(2), Some(2)));
+        assert_eq!(
+            e.with_day(3).to_string(),
+            "day 3, line 2, column 2, x".to_owned()
+        );
+        assert_eq!(Error::at_line(4, "y").line, Some(4));
+    }
+
+    #[test]
+    fn error_parse_all() {
+        assert_eq!(parse_all("1\n2\n", numbers("1\n2\n")), Ok(vec![1, 2]));
+
+        let input = "1\n2\nx3\n";
+        let e = parse_all(input, numbers(input)).unwrap_err();
+        assert_eq!((e.line, e.column), (Some(3), Some(1)));
+        assert_eq!(e.message, "could not parse 'x3'");
+
+        let e = parse_all("", numbers("")).unwrap_err();
+        assert_eq!(e.message, "unexpected end of input");
+
+        assert_eq!(finish(input, numbers(input)), Ok(("x3\n", vec![1, 2])));
+    }
+
+    #[test]
+    fn error_parse_lines() {
+        let p = |l: &str| l.parse::<u32>().map_err(|e| Error::new(e.to_string()));
+        assert_eq!(parse_lines("1\n2", p), Ok(vec![1, 2]));
+        assert_eq!(parse_lines("1\nx", p).unwrap_err().line, Some(2));
+
+        let e = parse_lines("1\n2\n3x", |l| parse_all(l, u32_val(l))).unwrap_err();
+        assert_eq!((e.line, e.column), (Some(3), Some(2)));
+    }
+}
@@ -0,0 +1,44 @@
+// Solutions to Advent of Code 2018. Every day implements solution::Solution and is listed in
+// solution::registry, the shared parsers and the opcode VM can be used on their own.
+
+pub mod aoc1;
+pub mod aoc10;
+pub mod aoc11;
+pub mod aoc12;
+pub mod aoc13;
+pub mod aoc14;
+pub mod aoc15;
+pub mod aoc16;
+pub mod aoc2;
+pub mod aoc3;
+pub mod aoc4;
+pub mod aoc5;
+pub mod aoc6;
+pub mod aoc7;
+pub mod aoc8;
+pub mod aoc9;
+// pub mod aoc18;
+pub mod aoc19;
+// pub mod aoc20;
+pub mod aoc21;
+pub mod aoc22;
+// pub mod aoc23;
+pub mod aoc24;
+pub mod aoc25;
+pub mod asm;
+pub mod bench;
+pub mod cfg;
+pub mod commands;
+pub mod compile;
+pub mod debugger;
+pub mod decompile;
+pub mod error;
+pub mod helper;
+pub mod opcodes;
+pub mod optimise;
+pub mod profile;
+pub mod report;
+pub mod runner;
+pub mod solution;
+pub mod trace;
+pub mod verify;
@@ -1,64 +1,201 @@
 use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
 
-mod aoc10;
-mod aoc11;
-mod aoc12;
-mod aoc13;
-mod aoc14;
-mod aoc15;
-mod aoc16;
-mod aoc3;
-mod aoc4;
-mod aoc5;
-mod aoc6;
-mod aoc7;
-mod aoc8;
-mod aoc9;
-// mod aoc18;
-mod aoc19;
-// mod aoc20;
-// mod aoc21;
-mod aoc22;
-// mod aoc23;
-mod aoc24;
-mod aoc25;
-mod helper;
-mod opcodes;
+use advent_of_code2018::{commands, opcodes, report, runner, verify};
+use opcodes::Overflow;
+use report::Format;
+use runner::InputSource;
+
+const USAGE: &str = "usage: advent_of_code2018 [--input <file|->] [--input-dir <dir>] \
+                     [--answers <file>] [--format <human|json>] \
+                     [--overflow <wrap|saturate|error>] [--timeout <seconds>] [--optimise] \
+                     <day|first-last|all|list|verify [days]|debug <program>|\
+                     profile <program> [steps] [r0]|decompile <program>|\
+                     dot <program> [output]|bench <program> [steps] [r0]|\
+                     run <program> [steps] [r0]|trace <program> [steps] [r0]|\
+                     diff <trace> <trace>|samples <day 16 input>>";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2);
+}
+
+fn exit_unless(ok: bool) {
+    if !ok {
+        process::exit(1);
+    }
+}
 
 fn main() {
+    let mut source = InputSource::default();
+    let mut answers_file = verify::DEFAULT_ANSWERS_FILE.to_owned();
+    let mut format = Format::Human;
+    let mut overflow = Overflow::default();
+    let mut timeout = None;
+    let mut optimise = false;
+    let mut positional = Vec::new();
+
     let mut a = env::args();
     a.next();
+    while let Some(arg) = a.next() {
+        match arg.as_ref() {
+            "--input" | "--input-dir" | "--answers" | "--format" | "--overflow" | "--timeout" => {
+                let value = match a.next() {
+                    Some(v) => v,
+                    None => {
+                        eprintln!("{} needs a value", arg);
+                        usage()
+                    }
+                };
+                match arg.as_ref() {
+                    "--input" => source = InputSource::from_arg(&value),
+                    "--input-dir" => source = InputSource::Dir(PathBuf::from(value)),
+                    "--format" => {
+                        format = match Format::from_arg(&value) {
+                            Some(f) => f,
+                            None => {
+                                eprintln!("unknown format {}", value);
+                                usage()
+                            }
+                        }
+                    }
+                    "--overflow" => {
+                        overflow = match Overflow::from_arg(&value) {
+                            Some(o) => o,
+                            None => {
+                                eprintln!("unknown overflow policy {}", value);
+                                usage()
+                            }
+                        }
+                    }
+                    "--timeout" => {
+                        timeout = match value.parse::<f64>() {
+                            Ok(secs) if secs >= 0.0 => Some(Duration::from_secs_f64(secs)),
+                            _ => {
+                                eprintln!("--timeout needs a number of seconds");
+                                usage()
+                            }
+                        }
+                    }
+                    _ => answers_file = value,
+                }
+            }
+            "--optimise" => optimise = true,
+            _ => positional.push(arg),
+        }
+    }
+
+    let (verifying, arg) = match positional.first().map(|s| s.as_str()) {
+        Some("list") => {
+            commands::list();
+            return;
+        }
+        Some("debug") => {
+            match positional.get(1) {
+                Some(program_file) => exit_unless(commands::debug(program_file, overflow)),
+                None => usage(),
+            }
+            return;
+        }
+        Some("decompile") => {
+            match positional.get(1) {
+                Some(program_file) => exit_unless(commands::decompile(program_file)),
+                None => usage(),
+            }
+            return;
+        }
+        Some("samples") => {
+            match positional.get(1) {
+                Some(input_file) => exit_unless(commands::samples(input_file)),
+                None => usage(),
+            }
+            return;
+        }
+        Some("dot") => {
+            match positional.get(1) {
+                Some(program_file) => exit_unless(commands::dot(
+                    program_file,
+                    positional.get(2).map(|s| s.as_str()),
+                )),
+                None => usage(),
+            }
+            return;
+        }
+        Some("diff") => {
+            match (positional.get(1), positional.get(2)) {
+                (Some(left), Some(right)) => exit_unless(commands::diff(left, right)),
+                _ => usage(),
+            }
+            return;
+        }
+        Some(command @ "profile")
+        | Some(command @ "bench")
+        | Some(command @ "run")
+        | Some(command @ "trace") => {
+            let execute = |program_file: &str, steps, r0| match command {
+                "profile" => commands::profile(program_file, steps, r0, overflow),
+                "bench" => commands::bench(program_file, steps, r0),
+                "trace" => commands::trace(program_file, steps, r0, overflow, timeout, optimise),
+                _ => commands::run(program_file, steps, r0, overflow, timeout),
+            };
+            let numbers: Option<Vec<usize>> =
+                positional.iter().skip(2).map(|n| n.parse().ok()).collect();
+            let ok = match (positional.get(1), numbers.as_deref()) {
+                (Some(program_file), Some([])) => execute(program_file, None, 0),
+                (Some(program_file), Some([steps])) => execute(program_file, Some(*steps), 0),
+                (Some(program_file), Some([steps, r0])) => execute(program_file, Some(*steps), *r0),
+                _ => usage(),
+            };
+            exit_unless(ok);
+            return;
+        }
+        Some("verify") => (
+            true,
+            positional
+                .get(1)
+                .cloned()
+                .unwrap_or_else(|| "all".to_owned()),
+        ),
+        Some(s) => (false, s.to_owned()),
+        None => {
+            println!("{}", USAGE);
+            commands::list();
+            return;
+        }
+    };
+
+    let days = match runner::select_days(&arg) {
+        Some(days) => days,
+        None => {
+            eprintln!("Day {} is not implemented", arg);
+            commands::list();
+            process::exit(2);
+        }
+    };
+
+    if days.len() > 1 && !matches!(source, InputSource::Dir(_)) {
+        eprintln!("--input can only be used when running a single day");
+        process::exit(2);
+    }
 
-    let day = match a.next() {
-        Some(s) => usize::from_str_radix(&s, 10).unwrap(),
-        None => 0,
+    let answers = if verifying {
+        match commands::read_answers(&answers_file) {
+            Some(answers) => Some(answers),
+            None => process::exit(1),
+        }
+    } else {
+        None
     };
 
-    match day {
-        // 1 => aoc1::run(),
-        // 2 => aoc2::run(),
-        3 => aoc3::run(),
-        4 => aoc4::run(),
-        5 => aoc5::run(),
-        6 => aoc6::run(),
-        7 => aoc7::run(),
-        8 => aoc8::run(),
-        9 => aoc9::run(),
-        10 => aoc10::run(),
-        11 => aoc11::run(),
-        12 => aoc12::run(),
-        13 => aoc13::run(),
-        14 => aoc14::run(),
-        15 => aoc15::run(),
-        16 => aoc16::run(),
-        // 18 => aoc18::run(),
-        19 => aoc19::run(),
-        // 20 => aoc20::run(),
-        // 21 => aoc21::run(),
-        22 => aoc22::run(),
-        // 23 => aoc23::run(),
-        24 => aoc24::run(),
-        25 => aoc25::run(),
-        _ => (),
+    if format == Format::Json {
+        exit_unless(commands::solve_json(&days, &source, answers.as_ref()));
+    } else if let Some(answers) = answers {
+        exit_unless(commands::verify(&days, &source, &answers));
+    } else if days.len() == 1 {
+        exit_unless(commands::solve_one(&days[0], &source));
+    } else {
+        exit_unless(commands::solve_all(&days, &source));
     }
 }
@@ -1,84 +1,963 @@
-// use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant};
 
-pub type Registers = Vec<usize>;
+use crate::compile::Compiled;
+use crate::optimise::{self, Idiom};
+use crate::profile::Profile;
+use crate::trace::Trace;
 
-pub fn addr(regs: &mut Registers, a: usize, b: usize, c: usize) {
-    let v = regs[a] + regs[b];
+// The most registers a VM can have, day 16 uses 4 and day 19 6
+pub const MAX_REGISTERS: usize = 8;
+
+// A register file in a fixed-size array, it derefs to the `width` registers that exist. The
+// registers past the width stay zero.
+#[derive(Clone)]
+pub struct Registers {
+    file: [usize; MAX_REGISTERS],
+    width: usize,
+}
+
+impl Registers {
+    // Panics if `width` is over MAX_REGISTERS
+    pub fn new(width: usize) -> Self {
+        assert!(
+            width <= MAX_REGISTERS,
+            "{} registers, there can only be {}",
+            width,
+            MAX_REGISTERS
+        );
+        Registers {
+            file: [0; MAX_REGISTERS],
+            width,
+        }
+    }
+
+    // None if there are more than MAX_REGISTERS values
+    pub fn from_slice(values: &[usize]) -> Option<Self> {
+        if values.len() > MAX_REGISTERS {
+            return None;
+        }
+        let mut regs = Registers::new(values.len());
+        regs.copy_from_slice(values);
+        Some(regs)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    // The whole array, for code that masks register numbers instead of checking them
+    pub(crate) fn file(&self) -> &[usize; MAX_REGISTERS] {
+        &self.file
+    }
+
+    pub(crate) fn file_mut(&mut self) -> &mut [usize; MAX_REGISTERS] {
+        &mut self.file
+    }
+}
+
+impl Deref for Registers {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        &self.file[..self.width]
+    }
+}
+
+impl DerefMut for Registers {
+    fn deref_mut(&mut self) -> &mut [usize] {
+        &mut self.file[..self.width]
+    }
+}
+
+impl PartialEq for Registers {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for Registers {}
+
+impl<const N: usize> PartialEq<[usize; N]> for Registers {
+    fn eq(&self, other: &[usize; N]) -> bool {
+        **self == other[..]
+    }
+}
+
+impl Hash for Registers {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state)
+    }
+}
+
+// Like a slice, [1, 2, 3, 4]
+impl fmt::Debug for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+// The operations without any checks, additions and multiplications wrap
+pub fn addr(regs: &mut [usize], a: usize, b: usize, c: usize) {
+    let v = regs[a].wrapping_add(regs[b]);
     regs[c] = v;
 }
 
-pub fn addi(regs: &mut Registers, a: usize, b: usize, c: usize) {
-    let v = regs[a] + b;
+pub fn addi(regs: &mut [usize], a: usize, b: usize, c: usize) {
+    let v = regs[a].wrapping_add(b);
     regs[c] = v;
 }
 
-pub fn mulr(regs: &mut Registers, a: usize, b: usize, c: usize) {
-    regs[c] = regs[a] * regs[b];
+pub fn mulr(regs: &mut [usize], a: usize, b: usize, c: usize) {
+    regs[c] = regs[a].wrapping_mul(regs[b]);
 }
 
-pub fn muli(regs: &mut Registers, a: usize, b: usize, c: usize) {
-    let v = regs[a] * b;
+pub fn muli(regs: &mut [usize], a: usize, b: usize, c: usize) {
+    let v = regs[a].wrapping_mul(b);
     regs[c] = v;
 }
 
-pub fn banr(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn banr(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = regs[a] & regs[b];
     regs[c] = v;
 }
 
-pub fn bani(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn bani(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = regs[a] & b;
     regs[c] = v;
 }
 
-pub fn borr(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn borr(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = regs[a] | regs[b];
     regs[c] = v;
 }
 
-pub fn bori(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn bori(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = regs[a] | b;
     regs[c] = v;
 }
 
-pub fn setr(regs: &mut Registers, a: usize, _: usize, c: usize) {
+pub fn setr(regs: &mut [usize], a: usize, _: usize, c: usize) {
     regs[c] = regs[a];
 }
 
-pub fn seti(regs: &mut Registers, a: usize, _: usize, c: usize) {
+pub fn seti(regs: &mut [usize], a: usize, _: usize, c: usize) {
     regs[c] = a;
 }
 
-pub fn gtir(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn gtir(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = if a > regs[b] { 1 } else { 0 };
     regs[c] = v;
 }
 
-pub fn gtri(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn gtri(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = if regs[a] > b { 1 } else { 0 };
     regs[c] = v;
 }
 
-pub fn gtrr(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn gtrr(regs: &mut [usize], a: usize, b: usize, c: usize) {
     regs[c] = if regs[a] > regs[b] { 1 } else { 0 };
 }
 
-pub fn eqir(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn eqir(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = if a == regs[b] { 1 } else { 0 };
     regs[c] = v;
 }
 
-pub fn eqri(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn eqri(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = if regs[a] == b { 1 } else { 0 };
     regs[c] = v;
 }
 
-pub fn eqrr(regs: &mut Registers, a: usize, b: usize, c: usize) {
+pub fn eqrr(regs: &mut [usize], a: usize, b: usize, c: usize) {
     let v = if regs[a] == regs[b] { 1 } else { 0 };
     regs[c] = v;
 }
 
-// pub fn neqr(regs: &mut Registers, a: usize, b: usize, c: usize) {
+// pub fn neqr(regs: &mut [usize], a: usize, b: usize, c: usize) {
 //     let v = if regs[a] != regs[b] { 1 } else { 0 };
 //     regs[c] = v;
 // }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Opcode {
+    Addr,
+    Addi,
+    Mulr,
+    Muli,
+    Banr,
+    Bani,
+    Borr,
+    Bori,
+    Setr,
+    Seti,
+    Gtir,
+    Gtri,
+    Gtrr,
+    Eqir,
+    Eqri,
+    Eqrr,
+}
+
+impl Opcode {
+    pub const ALL: [Opcode; 16] = [
+        Opcode::Addr,
+        Opcode::Addi,
+        Opcode::Mulr,
+        Opcode::Muli,
+        Opcode::Banr,
+        Opcode::Bani,
+        Opcode::Borr,
+        Opcode::Bori,
+        Opcode::Setr,
+        Opcode::Seti,
+        Opcode::Gtir,
+        Opcode::Gtri,
+        Opcode::Gtrr,
+        Opcode::Eqir,
+        Opcode::Eqri,
+        Opcode::Eqrr,
+    ];
+
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            Opcode::Addr => "addr",
+            Opcode::Addi => "addi",
+            Opcode::Mulr => "mulr",
+            Opcode::Muli => "muli",
+            Opcode::Banr => "banr",
+            Opcode::Bani => "bani",
+            Opcode::Borr => "borr",
+            Opcode::Bori => "bori",
+            Opcode::Setr => "setr",
+            Opcode::Seti => "seti",
+            Opcode::Gtir => "gtir",
+            Opcode::Gtri => "gtri",
+            Opcode::Gtrr => "gtrr",
+            Opcode::Eqir => "eqir",
+            Opcode::Eqri => "eqri",
+            Opcode::Eqrr => "eqrr",
+        }
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        Opcode::ALL
+            .iter()
+            .find(|op| op.mnemonic() == mnemonic)
+            .cloned()
+    }
+
+    // Whether the a and b operands name registers, otherwise they are immediate values
+    pub fn reads_a(self) -> bool {
+        !matches!(self, Opcode::Seti | Opcode::Gtir | Opcode::Eqir)
+    }
+
+    pub fn reads_b(self) -> bool {
+        matches!(
+            self,
+            Opcode::Addr
+                | Opcode::Mulr
+                | Opcode::Banr
+                | Opcode::Borr
+                | Opcode::Gtir
+                | Opcode::Gtrr
+                | Opcode::Eqir
+                | Opcode::Eqrr
+        )
+    }
+
+    // Comparisons always write 0 or 1
+    pub fn is_comparison(self) -> bool {
+        matches!(
+            self,
+            Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr | Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr
+        )
+    }
+
+    // Executes with every register access checked, the registers are unchanged on a fault. The
+    // ip register is not known here, see Vm::step for what it holds after a fault.
+    pub fn execute_checked(
+        self,
+        regs: &mut [usize],
+        a: usize,
+        b: usize,
+        c: usize,
+        overflow: Overflow,
+    ) -> Result<(), FaultKind> {
+        let read = |r: usize| regs.get(r).cloned().ok_or(FaultKind::Register(r));
+        let va = if self.reads_a() { read(a)? } else { a };
+        let vb = if self.reads_b() { read(b)? } else { b };
+        let (v, overflowed) = match self {
+            Opcode::Addr | Opcode::Addi => va.overflowing_add(vb),
+            Opcode::Mulr | Opcode::Muli => va.overflowing_mul(vb),
+            Opcode::Banr | Opcode::Bani => (va & vb, false),
+            Opcode::Borr | Opcode::Bori => (va | vb, false),
+            Opcode::Setr | Opcode::Seti => (va, false),
+            Opcode::Gtir | Opcode::Gtri | Opcode::Gtrr => ((va > vb) as usize, false),
+            Opcode::Eqir | Opcode::Eqri | Opcode::Eqrr => ((va == vb) as usize, false),
+        };
+        let v = overflow.resolve(v, overflowed)?;
+        *regs.get_mut(c).ok_or(FaultKind::Register(c))? = v;
+        Ok(())
+    }
+
+    pub fn execute(self, regs: &mut [usize], a: usize, b: usize, c: usize) {
+        let f = match self {
+            Opcode::Addr => addr,
+            Opcode::Addi => addi,
+            Opcode::Mulr => mulr,
+            Opcode::Muli => muli,
+            Opcode::Banr => banr,
+            Opcode::Bani => bani,
+            Opcode::Borr => borr,
+            Opcode::Bori => bori,
+            Opcode::Setr => setr,
+            Opcode::Seti => seti,
+            Opcode::Gtir => gtir,
+            Opcode::Gtri => gtri,
+            Opcode::Gtrr => gtrr,
+            Opcode::Eqir => eqir,
+            Opcode::Eqri => eqri,
+            Opcode::Eqrr => eqrr,
+        };
+        f(regs, a, b, c)
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic())
+    }
+}
+
+// What an addition or multiplication that does not fit a register results in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    Wrap,
+    Saturate,
+    #[default]
+    Error,
+}
+
+impl Overflow {
+    // `v` is the wrapped result
+    pub(crate) fn resolve(self, v: usize, overflowed: bool) -> Result<usize, FaultKind> {
+        match (overflowed, self) {
+            (false, _) | (true, Overflow::Wrap) => Ok(v),
+            (true, Overflow::Saturate) => Ok(usize::MAX),
+            (true, Overflow::Error) => Err(FaultKind::Overflow),
+        }
+    }
+
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "wrap" => Some(Overflow::Wrap),
+            "saturate" => Some(Overflow::Saturate),
+            "error" => Some(Overflow::Error),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    // An operand or the ip names a register that does not exist
+    Register(usize),
+    Overflow,
+}
+
+// Why the VM could not execute an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    pub ip: usize,
+    pub instruction: Instruction,
+    pub kind: FaultKind,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "fault at ip {} '{}': ", self.ip, self.instruction)?;
+        match self.kind {
+            FaultKind::Register(r) => write!(f, "there is no register {}", r),
+            FaultKind::Overflow => write!(f, "overflow"),
+        }
+    }
+}
+
+impl std::error::Error for Fault {}
+
+// Why a program can not be loaded into a VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    // More registers than MAX_REGISTERS
+    Width(usize),
+    // The ip is bound to a register that does not exist
+    IpRegister(usize),
+    // The instruction at `ip` names a register that does not exist
+    Register {
+        ip: usize,
+        instruction: Instruction,
+        register: usize,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Width(width) => write!(
+                f,
+                "{} registers, there can only be {}",
+                width, MAX_REGISTERS
+            ),
+            LoadError::IpRegister(r) => {
+                write!(f, "the ip is bound to r{}, which does not exist", r)
+            }
+            LoadError::Register {
+                ip,
+                instruction,
+                register,
+            } => write!(
+                f,
+                "'{}' at ip {} uses register {}, which does not exist",
+                instruction, ip, register
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Instruction {
+    pub op: Opcode,
+    pub a: usize,
+    pub b: usize,
+    pub c: usize,
+}
+
+impl Instruction {
+    pub fn new(op: Opcode, a: usize, b: usize, c: usize) -> Self {
+        Instruction { op, a, b, c }
+    }
+
+    pub fn execute(&self, regs: &mut [usize]) {
+        self.op.execute(regs, self.a, self.b, self.c)
+    }
+
+    pub fn execute_checked(&self, regs: &mut [usize], overflow: Overflow) -> Result<(), FaultKind> {
+        self.op
+            .execute_checked(regs, self.a, self.b, self.c, overflow)
+    }
+
+    // The first operand that names a register outside of `num_regs`
+    pub fn invalid_register(&self, num_regs: usize) -> Option<usize> {
+        self.reads()
+            .chain(std::iter::once(self.c))
+            .find(|r| *r >= num_regs)
+    }
+
+    // The registers the instruction reads
+    pub fn reads(&self) -> impl Iterator<Item = usize> {
+        let a = Some(self.a).filter(|_| self.op.reads_a());
+        let b = Some(self.b).filter(|_| self.op.reads_b());
+        a.into_iter().chain(b)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.op, self.a, self.b, self.c)
+    }
+}
+
+// Runs a program. If the ip is bound to a register (#ip N), that register holds the ip while
+// an instruction executes and the ip is read back from it afterwards.
+#[derive(Debug, Clone)]
+pub struct Vm {
+    pub regs: Registers,
+    pub program: Vec<Instruction>,
+    pub ip_reg: Option<usize>,
+    pub ip: usize,
+    // Collected while executing when set, see `profile`
+    pub profile: Option<Profile>,
+    // Every executed instruction when set, see `trace`
+    pub trace: Option<Trace>,
+    // Loops that run natively, indexed by the ip they start at, see `optimise`
+    pub idioms: Vec<Option<Idiom>>,
+    pub overflow: Overflow,
+}
+
+impl Vm {
+    // Checks that `width` registers can exist and that the ip register and every operand
+    // naming a register are among them. Only overflows fault while the program runs, unless the
+    // program or the registers are changed afterwards.
+    pub fn new(
+        program: Vec<Instruction>,
+        ip_reg: Option<usize>,
+        width: usize,
+    ) -> Result<Self, LoadError> {
+        if width > MAX_REGISTERS {
+            return Err(LoadError::Width(width));
+        }
+        if let Some(r) = ip_reg.filter(|r| *r >= width) {
+            return Err(LoadError::IpRegister(r));
+        }
+        for (ip, instruction) in program.iter().enumerate() {
+            if let Some(register) = instruction.invalid_register(width) {
+                return Err(LoadError::Register {
+                    ip,
+                    instruction: *instruction,
+                    register,
+                });
+            }
+        }
+        Ok(Vm {
+            regs: Registers::new(width),
+            program,
+            ip_reg,
+            ip: 0,
+            profile: None,
+            trace: None,
+            idioms: Vec::new(),
+            overflow: Overflow::default(),
+        })
+    }
+
+    // Recognises the loops in optimise::find, returns how many were found. Running such a loop
+    // counts as a single step and is not profiled.
+    pub fn optimise(&mut self) -> usize {
+        let idioms = optimise::find(&self.program, self.ip_reg);
+        self.idioms = vec![None; self.program.len()];
+        for idiom in idioms.iter() {
+            self.idioms[idiom.start()] = Some(*idiom);
+        }
+        idioms.len()
+    }
+
+    // Starts collecting a fresh profile
+    pub fn profile(&mut self) {
+        self.profile = Some(Profile::new(self.program.len(), self.regs.len()));
+    }
+
+    // Starts recording every step, a run of an idiom is recorded as one native step
+    pub fn trace(&mut self) {
+        self.trace = Some(Trace::default());
+    }
+
+    // Like `trace`, but writes every step to `out` as it runs instead of keeping it
+    pub fn trace_to(&mut self, out: impl io::Write + 'static) {
+        self.trace = Some(Trace::streamed(out));
+    }
+
+    pub fn halted(&self) -> bool {
+        self.ip >= self.program.len()
+    }
+
+    // Executes one instruction, returns false if the program has already halted. The ip stays
+    // at an instruction that faults. The ip is written to the ip register before the
+    // instruction runs, so after a fault that register holds the faulting ip while the other
+    // registers are unchanged.
+    pub fn step(&mut self) -> Result<bool, Fault> {
+        let instruction = match self.program.get(self.ip) {
+            Some(i) => *i,
+            None => return Ok(false),
+        };
+        let ip = self.ip;
+        let fault = move |kind| Fault {
+            ip,
+            instruction,
+            kind,
+        };
+        // Traces record the registers as the instruction sees them, with the ip written
+        if let Some(Some(idiom)) = self.idioms.get(self.ip) {
+            let before = self.trace.as_ref().map(|_| {
+                let mut regs = self.regs.clone();
+                if let Some(r) = self.ip_reg.and_then(|r| regs.get_mut(r)) {
+                    *r = ip;
+                }
+                regs
+            });
+            // A loop that would overflow runs instruction by instruction below
+            if let Some(next) = idiom.apply(&mut self.regs).map_err(fault)? {
+                self.ip = next;
+                if let (Some(t), Some(before)) = (self.trace.as_mut(), before) {
+                    t.record(ip, instruction, before, self.regs.clone(), true);
+                }
+                return Ok(true);
+            }
+        }
+        if let Some(r) = self.ip_reg {
+            *self
+                .regs
+                .get_mut(r)
+                .ok_or_else(|| fault(FaultKind::Register(r)))? = self.ip;
+        }
+        let before = self.trace.as_ref().map(|_| self.regs.clone());
+        let old = self.regs.get(instruction.c).cloned();
+        instruction
+            .execute_checked(&mut self.regs, self.overflow)
+            .map_err(fault)?;
+        if let (Some(p), Some(old)) = (self.profile.as_mut(), old) {
+            p.record(self.ip, instruction.c, old != self.regs[instruction.c]);
+        }
+        if let (Some(t), Some(before)) = (self.trace.as_mut(), before) {
+            t.record(ip, instruction, before, self.regs.clone(), false);
+        }
+        if let Some(r) = self.ip_reg {
+            self.ip = self.regs[r];
+        }
+        // An ip that does not fit halts the program
+        self.ip = self.ip.saturating_add(1);
+        Ok(true)
+    }
+
+    // Runs until the ip leaves the program, returns the number of executed instructions
+    pub fn run(&mut self) -> Result<usize, Fault> {
+        self.run_steps(usize::MAX)
+    }
+
+    // Like `run`, but decodes the program into closures first if it can, see compile::Compiled.
+    // Tracing needs every step and always runs `step`.
+    pub fn run_compiled(&mut self) -> Result<usize, Fault> {
+        match Compiled::new(self).filter(|_| self.trace.is_none()) {
+            Some(compiled) => compiled.run(self, usize::MAX),
+            None => self.run(),
+        }
+    }
+
+    // Runs at most `max` instructions, returns the number of executed instructions
+    pub fn run_steps(&mut self, max: usize) -> Result<usize, Fault> {
+        let mut steps = 0;
+        while steps < max && self.step()? {
+            steps += 1;
+        }
+        Ok(steps)
+    }
+
+    // Runs until the program halts or one of the limits in `options` is reached. Uses the
+    // compiled closures unless looking for loops, profiling or tracing.
+    pub fn run_with(&mut self, options: &RunOptions) -> Result<Outcome, Fault> {
+        // How many steps run between checking the time
+        const CHUNK: usize = 1 << 16;
+
+        let compiled = if options.detect_loops || self.profile.is_some() || self.trace.is_some() {
+            None
+        } else {
+            Compiled::new(self)
+        };
+        let limit = options.max_steps.unwrap_or(usize::MAX);
+        let start = Instant::now();
+        let mut steps = 0;
+        // Brent's algorithm, compares the state to one saved at steps that are powers of two
+        let mut saved = (self.ip, self.regs.clone());
+        let mut power = 1;
+        let mut period = 0;
+        loop {
+            if self.halted() {
+                return Ok(Outcome::Halted { steps });
+            }
+            if steps >= limit {
+                return Ok(Outcome::BudgetExhausted { steps });
+            }
+            if options.timeout.is_some_and(|t| start.elapsed() >= t) {
+                return Ok(Outcome::TimedOut { steps });
+            }
+
+            let n = CHUNK.min(limit - steps);
+            if let Some(compiled) = compiled.as_ref() {
+                steps += compiled.run(self, n)?;
+                continue;
+            }
+            for _ in 0..n {
+                if !self.step()? {
+                    break;
+                }
+                steps += 1;
+                if !options.detect_loops {
+                    continue;
+                }
+                period += 1;
+                if self.ip == saved.0 && self.regs == saved.1 {
+                    return Ok(Outcome::InfiniteLoop {
+                        ip: self.ip,
+                        period,
+                        steps,
+                    });
+                }
+                if period == power {
+                    saved = (self.ip, self.regs.clone());
+                    power *= 2;
+                    period = 0;
+                }
+            }
+        }
+    }
+}
+
+// Limits for Vm::run_with, the default runs until the program halts
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RunOptions {
+    pub max_steps: Option<usize>,
+    pub timeout: Option<Duration>,
+    // Stops when the ip and registers are the same as at an earlier step, then the program
+    // never halts. Takes at most twice the steps it takes to reach the loop and go around it.
+    pub detect_loops: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Halted {
+        steps: usize,
+    },
+    BudgetExhausted {
+        steps: usize,
+    },
+    TimedOut {
+        steps: usize,
+    },
+    // The state at `ip` repeats every `period` steps
+    InfiniteLoop {
+        ip: usize,
+        period: usize,
+        steps: usize,
+    },
+}
+
+impl Outcome {
+    pub fn steps(&self) -> usize {
+        match *self {
+            Outcome::Halted { steps }
+            | Outcome::BudgetExhausted { steps }
+            | Outcome::TimedOut { steps }
+            | Outcome::InfiniteLoop { steps, .. } => steps,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Outcome::Halted { steps } => write!(f, "halted after {} steps", steps),
+            Outcome::BudgetExhausted { steps } => {
+                write!(f, "still running after the budget of {} steps", steps)
+            }
+            Outcome::TimedOut { steps } => write!(f, "timed out after {} steps", steps),
+            Outcome::InfiniteLoop { ip, period, steps } => write!(
+                f,
+                "loops forever at ip {}, the state repeats every {} steps (found after {} steps)",
+                ip, period, steps
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opcodes_registers() {
+        let mut regs = Registers::from_slice(&[3, 2, 1, 1]).unwrap();
+        assert_eq!(regs.width(), 4);
+        mulr(&mut regs, 2, 1, 2);
+        assert_eq!(regs, [3, 2, 2, 1]);
+        assert_eq!(format!("{:?}", regs), "[3, 2, 2, 1]");
+        assert_eq!(regs.get(4), None);
+        assert_ne!(regs, Registers::new(4));
+        assert_ne!(Registers::new(4), Registers::new(6));
+        assert!(Registers::from_slice(&[0; MAX_REGISTERS + 1]).is_none());
+
+        // Registers past the width do not exist for the VM
+        let program = vec![Instruction::new(Opcode::Seti, 1, 0, 4)];
+        assert_eq!(
+            Vm::new(program.clone(), None, 4).unwrap_err(),
+            LoadError::Register {
+                ip: 0,
+                instruction: program[0],
+                register: 4
+            }
+        );
+        assert_eq!(
+            Vm::new(Vec::new(), Some(4), 4).unwrap_err(),
+            LoadError::IpRegister(4)
+        );
+        assert_eq!(
+            Vm::new(Vec::new(), None, MAX_REGISTERS + 1).unwrap_err(),
+            LoadError::Width(MAX_REGISTERS + 1)
+        );
+
+        // The operands are still checked when a changed program runs
+        let mut vm = Vm::new(Vec::new(), None, 4).unwrap();
+        vm.program = program;
+        assert_eq!(vm.run().unwrap_err().kind, FaultKind::Register(4));
+    }
+
+    #[test]
+    fn opcodes_mnemonic() {
+        for op in Opcode::ALL.iter() {
+            assert_eq!(Opcode::from_mnemonic(op.mnemonic()), Some(*op));
+        }
+        assert_eq!(Opcode::from_mnemonic("noop"), None);
+        assert_eq!(
+            Instruction::new(Opcode::Gtir, 1, 2, 3)
+                .reads()
+                .collect::<Vec<_>>(),
+            [2]
+        );
+        assert_eq!(
+            Instruction::new(Opcode::Addr, 1, 2, 3)
+                .reads()
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+        assert_eq!(Instruction::new(Opcode::Seti, 1, 2, 3).reads().count(), 0);
+        assert_eq!(
+            Instruction::new(Opcode::Addi, 2, 16, 2).to_string(),
+            "addi 2 16 2"
+        );
+    }
+
+    #[test]
+    fn opcodes_vm() {
+        let program = vec![
+            Instruction::new(Opcode::Seti, 5, 0, 1),
+            Instruction::new(Opcode::Seti, 6, 0, 2),
+            Instruction::new(Opcode::Addi, 0, 1, 0),
+            Instruction::new(Opcode::Addr, 1, 2, 3),
+            Instruction::new(Opcode::Setr, 1, 0, 0),
+            Instruction::new(Opcode::Seti, 8, 0, 4),
+            Instruction::new(Opcode::Seti, 9, 0, 5),
+        ];
+        let mut vm = Vm::new(program.clone(), Some(0), 6).unwrap();
+        assert_eq!(vm.step(), Ok(true));
+        assert_eq!((vm.ip, vm.regs[1]), (1, 5));
+        assert_eq!(vm.run(), Ok(4));
+        assert!(vm.halted());
+        assert_eq!(vm.step(), Ok(false));
+        assert_eq!(vm.regs, [6, 5, 6, 0, 0, 9]);
+
+        // Without a bound ip every instruction runs once
+        let mut vm = Vm::new(program, None, 6).unwrap();
+        assert_eq!(vm.run(), Ok(7));
+        assert_eq!(vm.regs, [5, 5, 6, 11, 8, 9]);
+    }
+
+    #[test]
+    fn opcodes_fault() {
+        let big = usize::MAX / 2 + 1;
+        let program = vec![
+            Instruction::new(Opcode::Seti, big, 0, 1),
+            Instruction::new(Opcode::Mulr, 1, 1, 2),
+        ];
+        let mut vm = Vm::new(program.clone(), None, 4).unwrap();
+        let fault = vm.run().unwrap_err();
+        assert_eq!(
+            fault,
+            Fault {
+                ip: 1,
+                instruction: program[1],
+                kind: FaultKind::Overflow
+            }
+        );
+        assert_eq!(fault.to_string(), "fault at ip 1 'mulr 1 1 2': overflow");
+        assert_eq!((vm.ip, vm.regs[2]), (1, 0));
+        // A bound ip register already holds the faulting ip
+        let mut bound = Vm::new(program.clone(), Some(3), 4).unwrap();
+        assert!(bound.run().is_err());
+        assert_eq!((bound.ip, bound.regs[2], bound.regs[3]), (1, 0, 1));
+
+        vm.overflow = Overflow::Saturate;
+        assert_eq!(vm.step(), Ok(true));
+        assert_eq!(vm.regs[2], usize::MAX);
+        // Only a program changed after loading can name a register that does not exist
+        vm.program.push(Instruction::new(Opcode::Setr, 4, 0, 3));
+        let fault = vm.run().unwrap_err();
+        assert_eq!((fault.ip, fault.kind), (2, FaultKind::Register(4)));
+        assert_eq!(
+            fault.to_string(),
+            "fault at ip 2 'setr 4 0 3': there is no register 4"
+        );
+
+        let mut vm = Vm::new(program, None, 4).unwrap();
+        vm.overflow = Overflow::Wrap;
+        assert_eq!(vm.run_steps(2), Ok(2));
+        assert_eq!(vm.regs[2], 0);
+
+        // Immediate values are not registers
+        let i = Instruction::new(Opcode::Gtir, 9, 3, 2);
+        assert_eq!(i.invalid_register(4), None);
+        assert_eq!(i.invalid_register(3), Some(3));
+        assert_eq!(
+            Instruction::new(Opcode::Seti, 9, 9, 6).invalid_register(6),
+            Some(6)
+        );
+
+        // A bound ip that does not fit halts the program
+        let program = vec![Instruction::new(Opcode::Seti, usize::MAX, 0, 0)];
+        let mut vm = Vm::new(program, Some(0), 1).unwrap();
+        assert_eq!(vm.run(), Ok(1));
+        assert!(vm.halted());
+    }
+
+    #[test]
+    fn opcodes_outcome() {
+        // Increments r0 forever, without ever repeating a state
+        let counter = vec![
+            Instruction::new(Opcode::Seti, 0, 0, 1),
+            Instruction::new(Opcode::Addi, 0, 1, 0),
+            Instruction::new(Opcode::Seti, 0, 0, 2),
+        ];
+        let budget = RunOptions {
+            max_steps: Some(1000),
+            ..RunOptions::default()
+        };
+        let checked = RunOptions {
+            detect_loops: true,
+            ..budget
+        };
+        let mut vm = Vm::new(counter, Some(2), 3).unwrap();
+        let mut looking = vm.clone();
+        assert_eq!(
+            vm.run_with(&budget),
+            Ok(Outcome::BudgetExhausted { steps: 1000 })
+        );
+        assert_eq!(
+            looking.run_with(&checked),
+            Ok(Outcome::BudgetExhausted { steps: 1000 })
+        );
+        assert_eq!((looking.ip, &looking.regs), (vm.ip, &vm.regs));
+        assert_eq!(vm.regs[0], 500);
+
+        // Jumps to itself
+        let program = vec![
+            Instruction::new(Opcode::Seti, 5, 0, 1),
+            Instruction::new(Opcode::Seti, 0, 0, 0),
+        ];
+        let mut vm = Vm::new(program, Some(0), 2).unwrap();
+        let outcome = vm.run_with(&checked).unwrap();
+        assert_eq!(
+            outcome,
+            Outcome::InfiniteLoop {
+                ip: 1,
+                period: 1,
+                steps: 2
+            }
+        );
+        assert_eq!(
+            outcome.to_string(),
+            "loops forever at ip 1, the state repeats every 1 steps (found after 2 steps)"
+        );
+
+        let timeout = RunOptions {
+            timeout: Some(Duration::from_secs(0)),
+            ..RunOptions::default()
+        };
+        assert_eq!(vm.run_with(&timeout), Ok(Outcome::TimedOut { steps: 0 }));
+
+        let mut vm = Vm::new(Vec::new(), None, 1).unwrap();
+        assert_eq!(vm.run_with(&timeout), Ok(Outcome::Halted { steps: 0 }));
+    }
+}
@@ -0,0 +1,454 @@
+use crate::opcodes::{FaultKind, Instruction, Opcode};
+
+// Loops that are known to compute something simple, the VM runs them natively when it reaches
+// their first instruction. Registers are named after the day 19 program:
+//
+// x = 1
+// do {
+//     y = 1
+//     do {
+//         if x * y == n {
+//             s += x
+//         }
+//         y += 1
+//     } while y <= n
+//     x += 1
+// } while x <= n
+//
+// Both loops are recognised, the outer one adds the sum of the divisors of n to s and the
+// inner one adds x to s if x divides n. The day 21 program divides by counting up instead:
+//
+// do {
+//     t = (q + 1) * d
+//     if t > n {
+//         break
+//     }
+//     q += 1
+// } while true
+//
+// which leaves q at n / d unless it starts out larger. The registers are left the way the
+// loops leave them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Idiom {
+    SumOfDivisors(Loop),
+    DivisorStep(Loop),
+    Division(Division),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Loop {
+    // The ip of the first instruction and the one to continue at
+    pub start: usize,
+    pub exit: usize,
+    pub ip_reg: usize,
+    pub x: usize,
+    pub y: usize,
+    pub n: usize,
+    pub s: usize,
+    // Scratch register for products and comparisons
+    pub t: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Division {
+    pub start: usize,
+    pub exit: usize,
+    pub ip_reg: usize,
+    pub q: usize,
+    pub t: usize,
+    pub n: usize,
+    // The divisor is an immediate value
+    pub d: usize,
+}
+
+fn is(i: &Instruction, op: Opcode, a: usize, b: usize, c: usize) -> bool {
+    i.op == op && i.a == a && i.b == b && i.c == c
+}
+
+// Commutative operations can have their operands either way around
+fn is_either(i: &Instruction, op: Opcode, a: usize, b: usize, c: usize) -> bool {
+    is(i, op, a, b, c) || is(i, op, b, a, c)
+}
+
+// `ip += t`
+fn is_skip(i: &Instruction, t: usize, ip_reg: usize) -> bool {
+    is_either(i, Opcode::Addr, t, ip_reg, ip_reg)
+}
+
+fn is_jump(i: &Instruction, target: usize, ip_reg: usize) -> bool {
+    i.op == Opcode::Seti && i.c == ip_reg && i.a + 1 == target
+}
+
+fn distinct(regs: &[usize]) -> bool {
+    regs.iter()
+        .enumerate()
+        .all(|(n, r)| !regs[n + 1..].contains(r))
+}
+
+// mulr x y t, eqrr t n t, addr t ip ip, addi ip 1 ip, addr x s s, addi y 1 y, gtrr y n t,
+// addr ip t ip, seti start-1 _ ip
+fn inner_loop(code: &[Instruction], start: usize, ip_reg: usize) -> Option<Loop> {
+    if code.len() < 9 || code[0].op != Opcode::Mulr {
+        return None;
+    }
+    let (x, y, t) = (code[0].a, code[0].b, code[0].c);
+    let n = if code[1].a == t { code[1].b } else { code[1].a };
+    let s = if code[4].a == x { code[4].b } else { code[4].a };
+    let l = Loop {
+        start,
+        exit: start + 9,
+        ip_reg,
+        x,
+        y,
+        n,
+        s,
+        t,
+    };
+    let matches = is_either(&code[1], Opcode::Eqrr, t, n, t)
+        && is_skip(&code[2], t, ip_reg)
+        && is(&code[3], Opcode::Addi, ip_reg, 1, ip_reg)
+        && is_either(&code[4], Opcode::Addr, x, s, s)
+        && is(&code[5], Opcode::Addi, y, 1, y)
+        && is(&code[6], Opcode::Gtrr, y, n, t)
+        && is_skip(&code[7], t, ip_reg)
+        && is_jump(&code[8], start, ip_reg)
+        && distinct(&[x, y, t, n, s, ip_reg]);
+    if matches {
+        Some(l)
+    } else {
+        None
+    }
+}
+
+// seti 1 _ x, seti 1 _ y, the inner loop, addi x 1 x, gtrr x n t, addr t ip ip,
+// seti start _ ip
+fn outer_loop(code: &[Instruction], start: usize, ip_reg: usize) -> Option<Loop> {
+    if code.len() < 15 {
+        return None;
+    }
+    let inner = inner_loop(&code[2..], start + 2, ip_reg)?;
+    let Loop { x, y, n, t, .. } = inner;
+    let matches = is(&code[0], Opcode::Seti, 1, code[0].b, x)
+        && is(&code[1], Opcode::Seti, 1, code[1].b, y)
+        && is(&code[11], Opcode::Addi, x, 1, x)
+        && is(&code[12], Opcode::Gtrr, x, n, t)
+        && is_skip(&code[13], t, ip_reg)
+        && is_jump(&code[14], start + 1, ip_reg);
+    if matches {
+        Some(Loop {
+            start,
+            exit: start + 15,
+            ..inner
+        })
+    } else {
+        None
+    }
+}
+
+// addi q 1 t, muli t d t, gtrr t n t, addr t ip ip, addi ip 1 ip, seti exit-1 _ ip,
+// addi q 1 q, seti start-1 _ ip
+fn division(code: &[Instruction], start: usize, ip_reg: usize) -> Option<Division> {
+    if code.len() < 8 || code[0].op != Opcode::Addi {
+        return None;
+    }
+    let (q, t, n, d) = (code[0].a, code[0].c, code[2].b, code[1].b);
+    let exit = code[5].a + 1;
+    let matches = is(&code[0], Opcode::Addi, q, 1, t)
+        && is(&code[1], Opcode::Muli, t, d, t)
+        && is(&code[2], Opcode::Gtrr, t, n, t)
+        && is_skip(&code[3], t, ip_reg)
+        && is(&code[4], Opcode::Addi, ip_reg, 1, ip_reg)
+        && is_jump(&code[5], exit, ip_reg)
+        && is(&code[6], Opcode::Addi, q, 1, q)
+        && is_jump(&code[7], start, ip_reg)
+        // The loop never ends when dividing by 0
+        && d > 0
+        && distinct(&[q, t, n, ip_reg]);
+    if matches {
+        Some(Division {
+            start,
+            exit,
+            ip_reg,
+            q,
+            t,
+            n,
+            d,
+        })
+    } else {
+        None
+    }
+}
+
+pub fn sum_of_divisors(n: usize) -> usize {
+    let mut sum = 0;
+    let mut d = 1;
+    while d * d <= n {
+        if n.is_multiple_of(d) {
+            sum += d;
+            if d * d != n {
+                sum += n / d;
+            }
+        }
+        d += 1;
+    }
+    sum
+}
+
+// The idioms in a program, there is nothing to recognise without a bound ip
+pub fn find(program: &[Instruction], ip_reg: Option<usize>) -> Vec<Idiom> {
+    let ip_reg = match ip_reg {
+        Some(r) => r,
+        None => return Vec::new(),
+    };
+    let mut idioms = Vec::new();
+    for start in 0..program.len() {
+        let code = &program[start..];
+        if let Some(l) = outer_loop(code, start, ip_reg) {
+            idioms.push(Idiom::SumOfDivisors(l));
+        }
+        if let Some(l) = inner_loop(code, start, ip_reg) {
+            idioms.push(Idiom::DivisorStep(l));
+        }
+        if let Some(d) = division(code, start, ip_reg) {
+            idioms.push(Idiom::Division(d));
+        }
+    }
+    idioms
+}
+
+impl Idiom {
+    pub fn start(&self) -> usize {
+        match self {
+            Idiom::SumOfDivisors(l) | Idiom::DivisorStep(l) => l.start,
+            Idiom::Division(d) => d.start,
+        }
+    }
+
+    // The registers the loop uses
+    fn registers(&self) -> Vec<usize> {
+        match *self {
+            Idiom::SumOfDivisors(l) | Idiom::DivisorStep(l) => {
+                vec![l.ip_reg, l.x, l.y, l.n, l.s, l.t]
+            }
+            Idiom::Division(d) => vec![d.ip_reg, d.q, d.t, d.n],
+        }
+    }
+
+    // Runs the loop starting at its first instruction, returns the ip to continue at. Returns
+    // None if an addition or multiplication in the loop would overflow, the loop has to be
+    // stepped through then so the overflow policy applies exactly as it would without idioms.
+    // The registers are only changed when the loop ran.
+    pub fn apply(&self, regs: &mut [usize]) -> Result<Option<usize>, FaultKind> {
+        if let Some(r) = self.registers().into_iter().find(|r| *r >= regs.len()) {
+            return Err(FaultKind::Register(r));
+        }
+        match *self {
+            Idiom::SumOfDivisors(l) => {
+                let n = regs[l.n];
+                // x * y goes up to n * n
+                if n.max(1).checked_mul(n.max(1)).is_none() {
+                    return Ok(None);
+                }
+                if n > 0 {
+                    regs[l.s] = match regs[l.s].checked_add(sum_of_divisors(n)) {
+                        Some(s) => s,
+                        None => return Ok(None),
+                    };
+                }
+                regs[l.x] = n.max(1) + 1;
+                regs[l.y] = n.max(1) + 1;
+                regs[l.t] = 1;
+                regs[l.ip_reg] = l.exit - 1;
+                Ok(Some(l.exit))
+            }
+            Idiom::DivisorStep(l) => {
+                let (x, y, n) = (regs[l.x], regs[l.y], regs[l.n]);
+                // The body runs for y..=n, and at least once
+                let last = y.max(n);
+                if x.checked_mul(last).is_none() || last.checked_add(1).is_none() {
+                    return Ok(None);
+                }
+                if x != 0 && n % x == 0 && y <= n / x && n / x <= last {
+                    regs[l.s] = match regs[l.s].checked_add(x) {
+                        Some(s) => s,
+                        None => return Ok(None),
+                    };
+                }
+                regs[l.y] = last + 1;
+                regs[l.t] = 1;
+                regs[l.ip_reg] = l.exit - 1;
+                Ok(Some(l.exit))
+            }
+            Idiom::Division(d) => {
+                // The last product is (q + 1) * d for the q the loop ends with
+                let q = regs[d.q].max(regs[d.n] / d.d);
+                if q.checked_add(1).and_then(|q| q.checked_mul(d.d)).is_none() {
+                    return Ok(None);
+                }
+                regs[d.q] = q;
+                regs[d.t] = 1;
+                regs[d.ip_reg] = d.exit - 1;
+                Ok(Some(d.exit))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+    use crate::compile::Compiled;
+    use crate::opcodes::{Overflow, Registers, Vm};
+
+    fn day19() -> Vm {
+        asm::parse(&std::fs::read_to_string("inputs/day19.txt").unwrap())
+            .unwrap()
+            .vm()
+            .unwrap()
+    }
+
+    #[test]
+    fn optimise_find() {
+        let vm = day19();
+        let idioms = find(&vm.program, vm.ip_reg);
+        let l = Loop {
+            start: 1,
+            exit: 16,
+            ip_reg: 2,
+            x: 4,
+            y: 5,
+            n: 3,
+            s: 0,
+            t: 1,
+        };
+        assert_eq!(
+            idioms,
+            [
+                Idiom::SumOfDivisors(l),
+                Idiom::DivisorStep(Loop {
+                    start: 3,
+                    exit: 12,
+                    ..l
+                })
+            ]
+        );
+        assert!(find(&vm.program, None).is_empty());
+        assert_eq!(find(&vm.program[..15], vm.ip_reg).len(), 1);
+
+        assert_eq!(sum_of_divisors(1), 1);
+        assert_eq!(sum_of_divisors(16), 31);
+        assert_eq!(sum_of_divisors(836), 1680);
+    }
+
+    // Runs from `start` until the ip is `exit`, instruction by instruction and with the idiom
+    fn compare(idiom: &Idiom, regs: &[usize]) {
+        compare_in(day19(), idiom, regs)
+    }
+
+    fn compare_in(mut vm: Vm, idiom: &Idiom, regs: &[usize]) {
+        vm.regs = Registers::from_slice(regs).unwrap();
+        vm.ip = idiom.start();
+        let mut native = vm.regs.clone();
+        let ip = idiom.apply(&mut native).unwrap().unwrap();
+        while vm.ip != ip {
+            assert_eq!(vm.step(), Ok(true));
+        }
+        assert_eq!(vm.regs, native, "{:?} from {:?}", idiom, regs);
+    }
+
+    #[test]
+    fn optimise_apply() {
+        let vm = day19();
+        let idioms = find(&vm.program, vm.ip_reg);
+        for n in 0..30 {
+            compare(&idioms[0], &[7, 0, 0, n, 0, 0]);
+            for x in 0..5 {
+                for y in 0..8 {
+                    compare(&idioms[1], &[7, 0, 0, n, x, y]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn optimise_overflow() {
+        let vm = day19();
+        let idioms = find(&vm.program, vm.ip_reg);
+        let mut regs = [7, 0, 0, usize::MAX, 0, 0];
+        assert_eq!(idioms[0].apply(&mut regs), Ok(None));
+        assert_eq!(regs, [7, 0, 0, usize::MAX, 0, 0]);
+        assert_eq!(idioms[1].apply(&mut [0; 5]), Err(FaultKind::Register(5)));
+
+        // x * y overflows on the first round, the policy decides what happens then
+        let regs = [0, 0, 0, 3, usize::MAX / 2 + 1, 2];
+        for overflow in [Overflow::Wrap, Overflow::Saturate, Overflow::Error].iter() {
+            let mut plain = day19();
+            plain.regs = Registers::from_slice(&regs).unwrap();
+            plain.ip = 3;
+            plain.overflow = *overflow;
+            let mut optimised = plain.clone();
+            optimised.optimise();
+            assert_eq!(optimised.run_steps(50), plain.run_steps(50));
+            assert_eq!((&optimised.regs, optimised.ip), (&plain.regs, plain.ip));
+
+            let mut compiled = optimised.clone();
+            compiled.regs = Registers::from_slice(&regs).unwrap();
+            compiled.ip = 3;
+            let mut stepped = compiled.clone();
+            assert_eq!(
+                Compiled::new(&compiled).unwrap().run(&mut compiled, 50),
+                stepped.run_steps(50)
+            );
+            assert_eq!(compiled.regs, stepped.regs);
+        }
+    }
+
+    #[test]
+    fn optimise_division() {
+        // r5 = r4 / 3 the slow way, as in day 21
+        let listing = asm::parse(
+            "#ip 1
+seti 0 0 5
+addi 5 1 2
+muli 2 3 2
+gtrr 2 4 2
+addr 2 1 1
+addi 1 1 1
+seti 8 0 1
+addi 5 1 5
+seti 0 0 1
+setr 5 0 4
+",
+        )
+        .unwrap();
+        let idioms = find(&listing.instructions(), listing.ip_reg);
+        let d = Division {
+            start: 1,
+            exit: 9,
+            ip_reg: 1,
+            q: 5,
+            t: 2,
+            n: 4,
+            d: 3,
+        };
+        assert_eq!(idioms, [Idiom::Division(d)]);
+
+        let mut vm = listing.vm().unwrap();
+        vm.regs[4] = 1000;
+        vm.optimise();
+        assert_eq!(vm.run(), Ok(3));
+        assert_eq!(vm.regs[4], 333);
+
+        for n in 0..40 {
+            for q in 0..15 {
+                compare_in(listing.vm().unwrap(), &idioms[0], &[0, 0, 0, 0, n, q]);
+            }
+        }
+
+        // A divisor of 0 never ends the loop
+        let zero = listing.to_string().replace("muli 2 3 2", "muli 2 0 2");
+        let listing = asm::parse(&zero).unwrap();
+        assert!(find(&listing.instructions(), listing.ip_reg).is_empty());
+    }
+}
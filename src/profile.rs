@@ -0,0 +1,132 @@
+use crate::opcodes::Instruction;
+
+// Execution counts collected by the VM while it runs, see Vm::profile
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub steps: u64,
+    // Indexed by ip
+    pub hits: Vec<u64>,
+    // Indexed by register, how often an instruction wrote it and how often that changed it
+    pub writes: Vec<u64>,
+    pub changes: Vec<u64>,
+}
+
+impl Profile {
+    pub fn new(program_len: usize, num_regs: usize) -> Self {
+        Profile {
+            steps: 0,
+            hits: vec![0; program_len],
+            writes: vec![0; num_regs],
+            changes: vec![0; num_regs],
+        }
+    }
+
+    pub fn record(&mut self, ip: usize, reg: usize, changed: bool) {
+        self.steps += 1;
+        self.hits[ip] += 1;
+        self.writes[reg] += 1;
+        if changed {
+            self.changes[reg] += 1;
+        }
+    }
+
+    fn percentage(&self, n: u64) -> f64 {
+        if self.steps == 0 {
+            0.0
+        } else {
+            100.0 * n as f64 / self.steps as f64
+        }
+    }
+
+    // The program with the hits of each instruction, followed by the register writes
+    pub fn report(&self, program: &[Instruction]) -> String {
+        let mut s = format!(
+            "{:>4}  {:<16} {:>12} {:>7}\n",
+            "ip", "instruction", "hits", "%"
+        );
+        for (ip, (i, hits)) in program.iter().zip(self.hits.iter()).enumerate() {
+            s += &format!(
+                "{:>4}  {:<16} {:>12} {:>7.2}\n",
+                ip,
+                i.to_string(),
+                hits,
+                self.percentage(*hits)
+            );
+        }
+
+        s += &format!("\n{:>4}  {:>12} {:>12}\n", "reg", "writes", "changes");
+        for (r, (writes, changes)) in self.writes.iter().zip(self.changes.iter()).enumerate() {
+            s += &format!("{:>4}  {:>12} {:>12}\n", format!("r{}", r), writes, changes);
+        }
+
+        s += &format!("\n{} steps\n", self.steps);
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::asm;
+
+    #[test]
+    fn profile_report() {
+        // Counts r2 up to r1 in the loop at ip 1-5
+        let listing = asm::parse(
+            "#ip 0
+seti 3 0 1
+addi 2 1 2
+gtrr 1 2 3
+addr 3 0 0
+seti 5 0 0
+seti 0 0 0
+",
+        )
+        .unwrap();
+        let mut vm = listing.vm().unwrap();
+        vm.profile();
+        assert_eq!(vm.run(), Ok(13));
+        let profile = vm.profile.unwrap();
+        assert_eq!(profile.hits, [1, 3, 3, 3, 1, 2]);
+        assert_eq!(profile.writes, [6, 1, 3, 3, 0, 0]);
+        assert_eq!(profile.changes, [5, 1, 3, 2, 0, 0]);
+        assert_eq!(
+            profile.report(&vm.program),
+            "  ip  instruction              hits       %
+   0  seti 3 0 1                  1    7.69
+   1  addi 2 1 2                  3   23.08
+   2  gtrr 1 2 3                  3   23.08
+   3  addr 3 0 0                  3   23.08
+   4  seti 5 0 0                  1    7.69
+   5  seti 0 0 0                  2   15.38
+
+ reg        writes      changes
+  r0             6            5
+  r1             1            1
+  r2             3            3
+  r3             3            2
+  r4             0            0
+  r5             0            0
+
+13 steps
+"
+        );
+    }
+
+    #[test]
+    fn profile_budget() {
+        // Loops forever incrementing r0
+        let listing = asm::parse("#ip 2\nseti 0 0 3\naddi 0 1 0\nseti 0 0 2\n").unwrap();
+        let mut vm = listing.vm().unwrap();
+        assert_eq!(vm.run_steps(10), Ok(10));
+        assert!(vm.profile.is_none());
+
+        vm.profile();
+        assert_eq!(vm.run_steps(7), Ok(7));
+        let profile = vm.profile.unwrap();
+        assert_eq!(
+            (profile.steps, profile.hits.as_slice()),
+            (7, &[0, 3, 4][..])
+        );
+        assert_eq!(vm.regs[0], 8);
+    }
+}
@@ -0,0 +1,156 @@
+use serde::Serialize;
+
+use crate::error::{Error, Result};
+use crate::runner::DayResult;
+use crate::solution::Unsolved;
+use crate::verify::{check, Answers, Status as Verified};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Human,
+    // One json object per day and part and line
+    Json,
+}
+
+impl Format {
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "human" => Some(Format::Human),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Ok,
+    Unsolved,
+    Error,
+    // Only when verifying against an answers file
+    Pass,
+    Fail,
+    Missing,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Record {
+    pub day: usize,
+    pub part: usize,
+    pub status: Status,
+    pub answer: Option<String>,
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    // Parse time is shared by both parts, both are missing if the input could not be parsed
+    pub parse_ms: Option<f64>,
+    pub time_ms: Option<f64>,
+}
+
+impl Record {
+    fn new(day: usize, part: usize, answer: &Result<String>, answers: Option<&Answers>) -> Self {
+        let (status, expected) = match (answers, answer) {
+            (Some(answers), _) => match check(answers, day, part, answer) {
+                Verified::Pass => (Status::Pass, answers.get(day, part)),
+                Verified::Fail(_) => (Status::Fail, answers.get(day, part)),
+                Verified::Missing => (Status::Missing, None),
+            },
+            (None, Ok(a)) if *a == Unsolved.to_string() => (Status::Unsolved, None),
+            (None, Ok(_)) => (Status::Ok, None),
+            (None, Err(_)) => (Status::Error, None),
+        };
+        Record {
+            day,
+            part,
+            status,
+            answer: answer.as_ref().ok().cloned(),
+            error: answer.as_ref().err().map(Error::to_string),
+            expected: expected.map(str::to_owned),
+            parse_ms: None,
+            time_ms: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("records only contain strings and numbers")
+    }
+}
+
+fn ms(d: std::time::Duration) -> Option<f64> {
+    Some(d.as_secs_f64() * 1000.0)
+}
+
+// The records for both parts of a day, answers are checked if an answers file is given
+pub fn records(day: usize, result: &Result<DayResult>, answers: Option<&Answers>) -> Vec<Record> {
+    match result {
+        Ok(r) => vec![
+            Record {
+                parse_ms: ms(r.parse_time),
+                time_ms: ms(r.part_one_time),
+                ..Record::new(day, 1, &r.part_one, answers)
+            },
+            Record {
+                parse_ms: ms(r.parse_time),
+                time_ms: ms(r.part_two_time),
+                ..Record::new(day, 2, &r.part_two, answers)
+            },
+        ],
+        Err(e) => {
+            let failed = Err(e.clone());
+            vec![
+                Record::new(day, 1, &failed, answers),
+                Record::new(day, 2, &failed, answers),
+            ]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::run_day;
+    use crate::solution::find;
+
+    #[test]
+    fn report_records() {
+        let res = run_day(&find(12).unwrap(), "initial state: #..#\n\n...## => #\n");
+        let recs = records(12, &res, None);
+        assert_eq!(recs.len(), 2);
+        assert_eq!((recs[0].part, recs[0].status), (1, Status::Ok));
+        assert_eq!(recs[1].status, Status::Unsolved);
+        assert!(recs[1].parse_ms.is_some());
+
+        let json: serde_json::Value = serde_json::from_str(&recs[0].to_json()).unwrap();
+        assert_eq!(json["day"], 12);
+        assert_eq!(json["status"], "ok");
+        assert_eq!(json["error"], serde_json::Value::Null);
+        assert!(json.get("expected").is_none());
+
+        let res = run_day(&find(8).unwrap(), "2 x");
+        let recs = records(8, &res, None);
+        assert_eq!(recs[1].status, Status::Error);
+        assert_eq!(
+            recs[1].error.as_deref(),
+            Some("day 8, line 1, column 3, expected a number, found 'x'")
+        );
+        assert_eq!(recs[1].answer, None);
+    }
+
+    #[test]
+    fn report_verified() {
+        let answers = Answers::parse("[day8]\npart1 = 138\npart2 = 1").unwrap();
+        let res = run_day(&find(8).unwrap(), "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2");
+        let recs = records(8, &res, Some(&answers));
+        assert_eq!(recs[0].status, Status::Pass);
+        assert_eq!(recs[1].status, Status::Fail);
+        assert_eq!(recs[1].expected.as_deref(), Some("1"));
+        assert_eq!(recs[1].answer.as_deref(), Some("66"));
+
+        let res = run_day(&find(9).unwrap(), "x");
+        let recs = records(9, &res, None);
+        assert_eq!((recs[0].status, recs[0].parse_ms), (Status::Error, None));
+        let recs = records(9, &res, Some(&answers));
+        assert_eq!(recs[0].status, Status::Missing);
+    }
+}
@@ -0,0 +1,235 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::solution::{find, registry, Day};
+
+#[derive(Debug)]
+pub struct DayResult {
+    pub day: usize,
+    pub part_one: Result<String>,
+    pub part_two: Result<String>,
+    pub parse_time: Duration,
+    pub part_one_time: Duration,
+    pub part_two_time: Duration,
+}
+
+impl DayResult {
+    pub fn total_time(&self) -> Duration {
+        self.parse_time + self.part_one_time + self.part_two_time
+    }
+}
+
+pub const DEFAULT_INPUT_DIR: &str = "inputs";
+
+#[derive(Debug, PartialEq)]
+pub enum InputSource {
+    // Read dayN.txt from the directory
+    Dir(PathBuf),
+    File(PathBuf),
+    Stdin,
+}
+
+impl Default for InputSource {
+    fn default() -> Self {
+        InputSource::Dir(PathBuf::from(DEFAULT_INPUT_DIR))
+    }
+}
+
+impl InputSource {
+    // "-" means stdin
+    pub fn from_arg(arg: &str) -> Self {
+        if arg == "-" {
+            InputSource::Stdin
+        } else {
+            InputSource::File(PathBuf::from(arg))
+        }
+    }
+
+    pub fn describe(&self, day: &Day) -> String {
+        match self {
+            InputSource::Dir(dir) => day.input_file(dir).display().to_string(),
+            InputSource::File(path) => path.display().to_string(),
+            InputSource::Stdin => "<stdin>".to_owned(),
+        }
+    }
+
+    // Whether there is anything to read for the day, stdin always counts
+    pub fn has_input(&self, day: &Day) -> bool {
+        match self {
+            InputSource::Dir(dir) => day.input_file(dir).exists(),
+            InputSource::File(path) => path.exists(),
+            InputSource::Stdin => true,
+        }
+    }
+
+    pub fn read(&self, day: &Day) -> io::Result<String> {
+        match self {
+            InputSource::Dir(dir) => fs::read_to_string(day.input_file(dir)),
+            InputSource::File(path) => fs::read_to_string(path),
+            InputSource::Stdin => {
+                let mut input = String::new();
+                io::stdin().read_to_string(&mut input)?;
+                Ok(input)
+            }
+        }
+    }
+}
+
+fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let res = f();
+    (res, start.elapsed())
+}
+
+// Fails if the input can not be parsed, errors in the parts are kept in the result
+pub fn run_day(day: &Day, input: &str) -> Result<DayResult> {
+    let (parsed, parse_time) = timed(|| day.parse(input));
+    let parsed = parsed?;
+    let (part_one, part_one_time) = timed(|| parsed.part_one());
+    let (part_two, part_two_time) = timed(|| parsed.part_two());
+    Ok(DayResult {
+        day: day.day,
+        part_one,
+        part_two,
+        parse_time,
+        part_one_time,
+        part_two_time,
+    })
+}
+
+// Reads the input for the day and runs it, failing to read the input is an error for the day
+pub fn solve_day(day: &Day, source: &InputSource) -> Result<DayResult> {
+    let input = source.read(day).map_err(|e| {
+        Error::new(format!("could not read {}: {}", source.describe(day), e)).with_day(day.day)
+    })?;
+    run_day(day, &input)
+}
+
+// "all", a single day "7" or an inclusive range "3-11"
+pub fn select_days(arg: &str) -> Option<Vec<Day>> {
+    let (first, last) = if arg == "all" {
+        (0, usize::MAX)
+    } else if let Some(idx) = arg.find('-') {
+        let first = arg[..idx].parse().ok()?;
+        let last = arg[idx + 1..].parse().ok()?;
+        (first, last)
+    } else {
+        return find(arg.parse().ok()?).map(|d| vec![d]);
+    };
+
+    let days: Vec<Day> = registry()
+        .into_iter()
+        .filter(|d| d.day >= first && d.day <= last)
+        .collect();
+
+    if days.is_empty() {
+        None
+    } else {
+        Some(days)
+    }
+}
+
+pub fn display_answer(answer: &Result<String>) -> String {
+    match answer {
+        Ok(a) => a.clone(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.3}", d.as_secs_f64() * 1000.0)
+}
+
+pub fn print_table(results: &[DayResult]) {
+    println!(
+        "{:>5} {:>12} {:>12} {:>12} {:>12}",
+        "Day", "Parse (ms)", "Part 1 (ms)", "Part 2 (ms)", "Total (ms)"
+    );
+    for r in results {
+        println!(
+            "{:>5} {:>12} {:>12} {:>12} {:>12}",
+            r.day,
+            ms(r.parse_time),
+            ms(r.part_one_time),
+            ms(r.part_two_time),
+            ms(r.total_time())
+        );
+    }
+
+    let sum = |f: fn(&DayResult) -> Duration| results.iter().map(f).sum::<Duration>();
+    println!(
+        "{:>5} {:>12} {:>12} {:>12} {:>12}",
+        "Total",
+        ms(sum(|r| r.parse_time)),
+        ms(sum(|r| r.part_one_time)),
+        ms(sum(|r| r.part_two_time)),
+        ms(sum(DayResult::total_time))
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn days(arg: &str) -> Option<Vec<usize>> {
+        select_days(arg).map(|ds| ds.iter().map(|d| d.day).collect())
+    }
+
+    #[test]
+    fn runner_select_days() {
+        assert_eq!(days("3"), Some(vec![3]));
+        assert_eq!(days("3-6"), Some(vec![3, 4, 5, 6]));
+        assert_eq!(days("14-20"), Some(vec![14, 15, 16, 19]));
+        assert_eq!(days("all").map(|d| d.len()), Some(registry().len()));
+        assert_eq!(days("1-3"), Some(vec![1, 2, 3]));
+        assert_eq!(days("17"), None);
+        assert_eq!(days("x-3"), None);
+        assert_eq!(days("foo"), None);
+    }
+
+    #[test]
+    fn runner_input_source() {
+        let day = find(3).unwrap();
+        assert_eq!(InputSource::from_arg("-"), InputSource::Stdin);
+        assert_eq!(InputSource::from_arg("x.txt").describe(&day), "x.txt");
+        assert_eq!(
+            InputSource::Dir(PathBuf::from("other")).describe(&day),
+            PathBuf::from("other")
+                .join("day3.txt")
+                .display()
+                .to_string()
+        );
+
+        let inputs = InputSource::default();
+        assert!(inputs.has_input(&day));
+        assert!(!inputs.has_input(&find(1).unwrap()));
+        assert!(!InputSource::from_arg("missing.txt").has_input(&day));
+    }
+
+    #[test]
+    fn runner_run_day() {
+        let day = find(8).unwrap();
+        let res = run_day(&day, "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+        assert_eq!(res.part_one, Ok("138".to_owned()));
+        assert_eq!(res.part_two, Ok("66".to_owned()));
+        assert_eq!(
+            res.total_time(),
+            res.parse_time + res.part_one_time + res.part_two_time
+        );
+
+        let day = find(4).unwrap();
+        let e = run_day(
+            &day,
+            "[1518-11-01 00:00] Guard #10 begins shift\n[1518-11-01 00:05] sleeps\n",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            e.to_string(),
+            "day 4, line 2, column 20, could not parse 'sleeps'"
+        );
+    }
+}
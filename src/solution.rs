@@ -0,0 +1,139 @@
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+use crate::error::Result;
+use crate::*;
+
+// Implemented by every day. The input is parsed once and then shared by both parts.
+pub trait Solution {
+    type Input;
+    type Part1: Display;
+    type Part2: Display;
+
+    fn parse(input: &str) -> Result<Self::Input>;
+    fn part_one(input: &Self::Input) -> Result<Self::Part1>;
+    fn part_two(input: &Self::Input) -> Result<Self::Part2>;
+}
+
+// Answer for a part that has not been solved (yet)
+pub struct Unsolved;
+
+impl Display for Unsolved {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "unsolved")
+    }
+}
+
+// Type erased parsed input, so days with different input types can live in the same registry.
+// Errors are tagged with the day.
+pub trait Parsed {
+    fn part_one(&self) -> Result<String>;
+    fn part_two(&self) -> Result<String>;
+}
+
+struct ParsedInput<S: Solution> {
+    day: usize,
+    input: S::Input,
+}
+
+impl<S: Solution> Parsed for ParsedInput<S> {
+    fn part_one(&self) -> Result<String> {
+        S::part_one(&self.input)
+            .map(|a| a.to_string())
+            .map_err(|e| e.with_day(self.day))
+    }
+
+    fn part_two(&self) -> Result<String> {
+        S::part_two(&self.input)
+            .map(|a| a.to_string())
+            .map_err(|e| e.with_day(self.day))
+    }
+}
+
+pub struct Day {
+    pub day: usize,
+    parse: fn(usize, &str) -> Result<Box<dyn Parsed>>,
+}
+
+impl Day {
+    fn new<S: Solution + 'static>(day: usize) -> Self {
+        Day {
+            day,
+            parse: |day, input| match S::parse(input) {
+                Ok(input) => Ok(Box::new(ParsedInput::<S> { day, input })),
+                Err(e) => Err(e.with_day(day)),
+            },
+        }
+    }
+
+    // By convention the input for day N lives in <dir>/dayN.txt
+    pub fn input_file(&self, dir: &Path) -> PathBuf {
+        dir.join(format!("day{}.txt", self.day))
+    }
+
+    pub fn parse(&self, input: &str) -> Result<Box<dyn Parsed>> {
+        (self.parse)(self.day, input)
+    }
+}
+
+// All implemented days, sorted by day
+pub fn registry() -> Vec<Day> {
+    vec![
+        Day::new::<aoc1::Day1>(1),
+        Day::new::<aoc2::Day2>(2),
+        Day::new::<aoc3::Day3>(3),
+        Day::new::<aoc4::Day4>(4),
+        Day::new::<aoc5::Day5>(5),
+        Day::new::<aoc6::Day6>(6),
+        Day::new::<aoc7::Day7>(7),
+        Day::new::<aoc8::Day8>(8),
+        Day::new::<aoc9::Day9>(9),
+        Day::new::<aoc10::Day10>(10),
+        Day::new::<aoc11::Day11>(11),
+        Day::new::<aoc12::Day12>(12),
+        Day::new::<aoc13::Day13>(13),
+        Day::new::<aoc14::Day14>(14),
+        Day::new::<aoc15::Day15>(15),
+        Day::new::<aoc16::Day16>(16),
+        Day::new::<aoc19::Day19>(19),
+        Day::new::<aoc21::Day21>(21),
+        Day::new::<aoc22::Day22>(22),
+        Day::new::<aoc24::Day24>(24),
+        Day::new::<aoc25::Day25>(25),
+    ]
+}
+
+pub fn find(day: usize) -> Option<Day> {
+    registry().into_iter().find(|d| d.day == day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solution_registry_sorted() {
+        let days: Vec<usize> = registry().iter().map(|d| d.day).collect();
+        let mut sorted = days.clone();
+        sorted.sort();
+        sorted.dedup();
+        assert_eq!(days, sorted);
+        assert!(find(3).is_some());
+        assert!(find(1).is_some());
+        assert!(find(17).is_none());
+    }
+
+    #[test]
+    fn solution_parse_and_solve() {
+        let day = find(3).unwrap();
+        let parsed = day
+            .parse("#1 @ 1,3: 4x4\n#2 @ 3,1: 4x4\n#3 @ 5,5: 2x2")
+            .unwrap();
+        assert_eq!(parsed.part_one(), Ok("4".to_owned()));
+        assert_eq!(parsed.part_two(), Ok("3".to_owned()));
+
+        // Box<dyn Parsed> is not Debug, so unwrap_err can not be used
+        let e = day.parse("#1 @ 1,3: 4x4\n#2 @ 3,1 4x4").err().unwrap();
+        assert_eq!((e.day, e.line), (Some(3), Some(2)));
+    }
+}
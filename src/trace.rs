@@ -0,0 +1,424 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use nom::bytes::complete::tag;
+use nom::character::complete::{alpha1, space1};
+use nom::combinator::{map_opt, opt};
+use nom::multi::separated_list;
+use nom::IResult;
+
+use crate::error::{parse_all, parse_lines, Result};
+use crate::helper::usize_val;
+use crate::opcodes::{Instruction, Opcode, Registers};
+
+// One executed instruction, a line in the log:
+//
+// 5 3: mulr 4 5 1 [0, 1, 3, 10, 1, 1] -> [0, 1, 3, 10, 1, 1]
+//
+// An idiom that ran natively has the instruction it starts at and ends with ` native`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub step: usize,
+    pub ip: usize,
+    pub instruction: Instruction,
+    pub before: Registers,
+    pub after: Registers,
+    pub native: bool,
+}
+
+impl Entry {
+    // Everything but the step, which differs once a native step has stood in for several
+    fn same(&self, other: &Entry) -> bool {
+        (
+            self.ip,
+            self.instruction,
+            &self.before,
+            &self.after,
+            self.native,
+        ) == (
+            other.ip,
+            other.instruction,
+            &other.before,
+            &other.after,
+            other.native,
+        )
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {} {:?} -> {:?}",
+            self.step, self.ip, self.instruction, self.before, self.after
+        )?;
+        if self.native {
+            write!(f, " native")?;
+        }
+        Ok(())
+    }
+}
+
+// A trace line is around 60 bytes, tracing stops after this many steps unless asked for more
+pub const DEFAULT_MAX_STEPS: usize = 1_000_000;
+
+// Where a streamed trace writes its entries, shared by the clones of the trace
+struct Stream {
+    out: Box<dyn Write>,
+    // The first write that failed, nothing is written after it
+    error: Option<io::Error>,
+}
+
+impl Stream {
+    fn write(&mut self, entry: &Entry) {
+        if self.error.is_none() {
+            if let Err(e) = writeln!(self.out, "{}", entry) {
+                self.error = Some(e);
+            }
+        }
+    }
+}
+
+// Collected by the VM while it runs when set, see Vm::trace. A streamed trace writes every entry
+// as it is recorded and keeps none of them, see Vm::trace_to.
+#[derive(Clone, Default)]
+pub struct Trace {
+    pub entries: Vec<Entry>,
+    // The number of recorded entries, including those that were written out
+    steps: usize,
+    stream: Option<Rc<RefCell<Stream>>>,
+}
+
+impl fmt::Debug for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Trace")
+            .field("entries", &self.entries)
+            .field("steps", &self.steps)
+            .field("streamed", &self.stream.is_some())
+            .finish()
+    }
+}
+
+impl PartialEq for Trace {
+    fn eq(&self, other: &Trace) -> bool {
+        (&self.entries, self.steps) == (&other.entries, other.steps)
+    }
+}
+
+fn parse_regs(i: &str) -> IResult<&str, Registers> {
+    let (i, _) = tag("[")(i)?;
+    let (i, regs) = map_opt(separated_list(tag(", "), usize_val), |v| {
+        Registers::from_slice(&v)
+    })(i)?;
+    let (i, _) = tag("]")(i)?;
+    Ok((i, regs))
+}
+
+fn parse_entry(i: &str) -> IResult<&str, Entry> {
+    let (i, step) = usize_val(i)?;
+    let (i, _) = tag(" ")(i)?;
+    let (i, ip) = usize_val(i)?;
+    let (i, _) = tag(": ")(i)?;
+    let (i, op) = map_opt(alpha1, Opcode::from_mnemonic)(i)?;
+    let (i, _) = space1(i)?;
+    let (i, a) = usize_val(i)?;
+    let (i, _) = space1(i)?;
+    let (i, b) = usize_val(i)?;
+    let (i, _) = space1(i)?;
+    let (i, c) = usize_val(i)?;
+    let (i, _) = tag(" ")(i)?;
+    let (i, before) = parse_regs(i)?;
+    let (i, _) = tag(" -> ")(i)?;
+    let (i, after) = parse_regs(i)?;
+    let (i, native) = opt(tag(" native"))(i)?;
+    Ok((
+        i,
+        Entry {
+            step,
+            ip,
+            instruction: Instruction::new(op, a, b, c),
+            before,
+            after,
+            native: native.is_some(),
+        },
+    ))
+}
+
+impl Trace {
+    // A trace that writes each entry to `out` as it is recorded
+    pub fn streamed(out: impl Write + 'static) -> Self {
+        Trace {
+            stream: Some(Rc::new(RefCell::new(Stream {
+                out: Box::new(out),
+                error: None,
+            }))),
+            ..Trace::default()
+        }
+    }
+
+    pub fn record(
+        &mut self,
+        ip: usize,
+        instruction: Instruction,
+        before: Registers,
+        after: Registers,
+        native: bool,
+    ) {
+        let entry = Entry {
+            step: self.steps,
+            ip,
+            instruction,
+            before,
+            after,
+            native,
+        };
+        self.steps += 1;
+        match self.stream.as_ref() {
+            Some(stream) => stream.borrow_mut().write(&entry),
+            None => self.entries.push(entry),
+        }
+    }
+
+    // Flushes a streamed trace, returns the first error writing it
+    pub fn finish(&self) -> io::Result<()> {
+        match self.stream.as_ref() {
+            Some(stream) => {
+                let mut stream = stream.borrow_mut();
+                match stream.error.take() {
+                    Some(e) => Err(e),
+                    None => stream.out.flush(),
+                }
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn parse(input: &str) -> Result<Self> {
+        let entries = parse_lines(input, |line| parse_all(line, parse_entry(line)))?;
+        Ok(Trace {
+            steps: entries.len(),
+            entries,
+            stream: None,
+        })
+    }
+
+    // The ip the entry at `n` continued at, None if the trace ends there
+    fn next_ip(&self, n: usize) -> Option<usize> {
+        self.entries.get(n + 1).map(|e| e.ip)
+    }
+
+    // Where `native` ends up, found in the plain steps from `from` on
+    fn catch_up(&self, n: usize, plain: &Trace, from: usize) -> Option<usize> {
+        let native = &self.entries[n];
+        let first = plain.entries.get(from)?;
+        if (first.ip, &first.before) != (native.ip, &native.before) {
+            return None;
+        }
+        (from..plain.entries.len()).find(|m| {
+            plain.entries[*m].after == native.after && plain.next_ip(*m) == self.next_ip(n)
+        })
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for e in self.entries.iter() {
+            writeln!(f, "{}", e)?;
+        }
+        Ok(())
+    }
+}
+
+// The first entries in two traces that do not agree, None where a trace has ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub left: Option<Entry>,
+    pub right: Option<Entry>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (side, e) in [("<", &self.left), (">", &self.right)].iter() {
+            match e {
+                Some(e) => writeln!(f, "{} {}", side, e)?,
+                None => writeln!(f, "{} end of trace", side)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Compares two traces entry by entry. A native step in one trace matches the plain steps in
+// the other that start from the same state and end in the same one, so an optimised run can be
+// compared to a plain one.
+pub fn diff(left: &Trace, right: &Trace) -> Option<Divergence> {
+    let (mut l, mut r) = (0, 0);
+    loop {
+        let (a, b) = match (left.entries.get(l), right.entries.get(r)) {
+            (None, None) => return None,
+            (Some(a), Some(b)) => (a, b),
+            (a, b) => {
+                return Some(Divergence {
+                    left: a.cloned(),
+                    right: b.cloned(),
+                })
+            }
+        };
+        if a.same(b) {
+            l += 1;
+            r += 1;
+            continue;
+        }
+        let caught_up = match (a.native, b.native) {
+            (true, false) => left.catch_up(l, right, r).map(|m| (l + 1, m + 1)),
+            (false, true) => right.catch_up(r, left, l).map(|m| (m + 1, r + 1)),
+            _ => None,
+        };
+        match caught_up {
+            Some(next) => {
+                l = next.0;
+                r = next.1;
+            }
+            None => {
+                return Some(Divergence {
+                    left: Some(a.clone()),
+                    right: Some(b.clone()),
+                })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn trace_round_trip() {
+        let mut vm = asm::parse("#ip 0\nseti 5 0 1\naddi 0 1 0\nmulr 1 1 2\nseti 9 0 3\n")
+            .unwrap()
+            .vm()
+            .unwrap();
+        vm.trace();
+        vm.run().unwrap();
+        let trace = vm.trace.unwrap();
+        assert_eq!(
+            trace.to_string(),
+            "0 0: seti 5 0 1 [0, 0, 0, 0, 0, 0] -> [0, 5, 0, 0, 0, 0]
+1 1: addi 0 1 0 [1, 5, 0, 0, 0, 0] -> [2, 5, 0, 0, 0, 0]
+2 3: seti 9 0 3 [3, 5, 0, 0, 0, 0] -> [3, 5, 0, 9, 0, 0]
+"
+        );
+        assert_eq!(Trace::parse(&trace.to_string()), Ok(trace));
+
+        let e = Trace::parse("0 0: seti 5 0 1 [0] -> [5]\n1 1: noop 1 1 1 [5] -> [5]\n");
+        assert_eq!(e.unwrap_err().line, Some(2));
+        assert!(
+            Trace::parse("0 0: seti 5 0 1 [0] -> [5] native\n")
+                .unwrap()
+                .entries[0]
+                .native
+        );
+    }
+
+    // Collects what a streamed trace writes
+    #[derive(Clone, Default)]
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trace_stream() {
+        let listing =
+            asm::parse("#ip 0\nseti 5 0 1\naddi 0 1 0\nmulr 1 1 2\nseti 9 0 3\n").unwrap();
+        let mut vm = listing.vm().unwrap();
+        vm.trace();
+        vm.run().unwrap();
+        let kept = vm.trace.unwrap();
+
+        let out = Shared::default();
+        let mut vm = listing.vm().unwrap();
+        vm.trace_to(out.clone());
+        assert_eq!(vm.run_steps(2), Ok(2));
+        // Written as soon as they ran
+        let first_two: String = kept.entries[..2]
+            .iter()
+            .map(|e| format!("{}\n", e))
+            .collect();
+        assert_eq!(out.0.borrow().as_slice(), first_two.as_bytes());
+        vm.run().unwrap();
+        let streamed = vm.trace.unwrap();
+        assert!(streamed.entries.is_empty());
+        assert!(streamed.finish().is_ok());
+        assert_eq!(String::from_utf8(out.0.take()).unwrap(), kept.to_string());
+    }
+
+    #[test]
+    fn trace_diff() {
+        // r5 = r4 / 3 the slow way, which runs natively when optimised
+        let listing = asm::parse(
+            "#ip 1
+seti 0 0 5
+addi 5 1 2
+muli 2 3 2
+gtrr 2 4 2
+addr 2 1 1
+addi 1 1 1
+seti 8 0 1
+addi 5 1 5
+seti 0 0 1
+setr 5 0 4
+",
+        )
+        .unwrap();
+        let mut plain = listing.vm().unwrap();
+        plain.regs[4] = 1000;
+        plain.trace();
+        plain.run().unwrap();
+        let plain = plain.trace.unwrap();
+        let mut optimised = listing.vm().unwrap();
+        optimised.regs[4] = 1000;
+        optimised.optimise();
+        optimised.trace();
+        optimised.run().unwrap();
+        let optimised = optimised.trace.unwrap();
+        assert_eq!(optimised.entries.len(), 3);
+        assert!(plain.entries.len() > 2000);
+        assert_eq!(diff(&plain, &optimised), None);
+        assert_eq!(diff(&optimised, &plain), None);
+        assert_eq!(diff(&plain, &plain), None);
+
+        // A wrong native result is reported where the native step starts
+        let mut wrong = optimised.clone();
+        let n = wrong.entries.iter().position(|e| e.native).unwrap();
+        wrong.entries[n].after[0] += 1;
+        let d = diff(&plain, &wrong).unwrap();
+        assert_eq!(d.left.as_ref(), Some(&plain.entries[n]));
+        assert_eq!(d.right.as_ref(), Some(&wrong.entries[n]));
+
+        let mut short = plain.clone();
+        let last = short.entries.pop();
+        assert_eq!(
+            diff(&plain, &short),
+            Some(Divergence {
+                left: last.clone(),
+                right: None
+            })
+        );
+        assert_eq!(
+            diff(&plain, &short).unwrap().to_string(),
+            format!("< {}\n> end of trace\n", last.unwrap())
+        );
+    }
+}
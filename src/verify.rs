@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+
+use crate::error::{Error, Result};
+use crate::runner::{display_answer, DayResult};
+
+pub const DEFAULT_ANSWERS_FILE: &str = "answers.toml";
+
+// Expected answers per (day, part), read from a toml file on the form
+//
+// [day3]
+// part1 = "120408"
+// part2 = 1276
+#[derive(Debug, Default)]
+pub struct Answers {
+    answers: HashMap<(usize, usize), String>,
+}
+
+fn parse_key(key: &str, prefix: &str) -> Result<usize> {
+    key.strip_prefix(prefix)
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| Error::new(format!("expected {}<number>, found {}", prefix, key)))
+}
+
+impl Answers {
+    pub fn parse(input: &str) -> Result<Self> {
+        let table: toml::Table = input.parse().map_err(|e: toml::de::Error| match e.span() {
+            Some(span) => Error::at(input, &input[span.start..], e.message()),
+            None => Error::new(e.message()),
+        })?;
+
+        let mut answers = HashMap::new();
+        for (day_key, parts) in table.iter() {
+            let day = parse_key(day_key, "day")?;
+            let parts = parts
+                .as_table()
+                .ok_or_else(|| Error::new(format!("[{}] is not a table", day_key)))?;
+            for (part_key, answer) in parts.iter() {
+                let part = parse_key(part_key, "part")?;
+                let answer = match answer {
+                    toml::Value::String(s) => s.clone(),
+                    toml::Value::Integer(i) => i.to_string(),
+                    other => {
+                        return Err(Error::new(format!(
+                            "{}.{} must be a string or an integer, found {}",
+                            day_key,
+                            part_key,
+                            other.type_str()
+                        )))
+                    }
+                };
+                answers.insert((day, part), answer);
+            }
+        }
+        Ok(Answers { answers })
+    }
+
+    pub fn get(&self, day: usize, part: usize) -> Option<&str> {
+        self.answers.get(&(day, part)).map(|s| s.as_str())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Status {
+    Pass,
+    Fail(String),
+    Missing,
+}
+
+// Leading and trailing whitespace is not significant, multi line answers (day 10) are stored
+// in toml multi line strings.
+pub fn check(answers: &Answers, day: usize, part: usize, answer: &Result<String>) -> Status {
+    match (answers.get(day, part), answer) {
+        (None, _) => Status::Missing,
+        (Some(expected), Ok(answer)) if expected.trim() == answer.trim() => Status::Pass,
+        (Some(expected), _) => Status::Fail(expected.to_owned()),
+    }
+}
+
+// Prints a line per day and part, returns the number of failed parts. A day that could not be
+// solved fails every part with an answer, the days in `no_input` were not run and are missing.
+pub fn report(
+    answers: &Answers,
+    results: &[(usize, Result<DayResult>)],
+    no_input: &[usize],
+) -> usize {
+    let mut passed = 0;
+    let mut failed = 0;
+    let mut missing = 0;
+    for (day, res) in results {
+        let parts = match res {
+            Ok(r) => [r.part_one.clone(), r.part_two.clone()],
+            Err(e) => [Err(e.clone()), Err(e.clone())],
+        };
+        for (part, answer) in [(1, &parts[0]), (2, &parts[1])].iter() {
+            match check(answers, *day, *part, answer) {
+                Status::Pass => {
+                    passed += 1;
+                    println!("{}:{} - pass", day, part);
+                }
+                Status::Fail(expected) => {
+                    failed += 1;
+                    println!(
+                        "{}:{} - FAIL, expected {} got {}",
+                        day,
+                        part,
+                        expected,
+                        display_answer(answer)
+                    );
+                }
+                Status::Missing => {
+                    missing += 1;
+                    println!("{}:{} - missing", day, part);
+                }
+            }
+        }
+    }
+    for day in no_input {
+        missing += 2;
+        println!("{} - missing input", day);
+    }
+    println!("{} passed, {} failed, {} missing", passed, failed, missing);
+    failed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::run_day;
+    use crate::solution::find;
+
+    #[test]
+    fn verify_parse() {
+        let answers = Answers::parse(
+            r#"
+[day3]
+part1 = "120408"
+part2 = 1276
+
+[day10]
+part1 = """
+..*..
+.*.*."""
+"#,
+        )
+        .unwrap();
+        assert_eq!(answers.get(3, 1), Some("120408"));
+        assert_eq!(answers.get(3, 2), Some("1276"));
+        assert_eq!(answers.get(10, 1), Some("..*..\n.*.*."));
+        assert_eq!(answers.get(10, 2), None);
+
+        assert!(Answers::parse("[three]\npart1 = 1").is_err());
+        assert!(Answers::parse("[day3]\none = 1").is_err());
+        assert!(Answers::parse("[day3]\npart1 = 1.5").is_err());
+        let e = Answers::parse("[day3]\npart1 = 1\n[day4\n").unwrap_err();
+        assert_eq!(e.line, Some(3));
+    }
+
+    #[test]
+    fn verify_check() {
+        let answers = Answers::parse("[day3]\npart1 = 4\n[day10]\npart1 = '''\n*.\n.*'''").unwrap();
+        let ok = |s: &str| Ok(s.to_owned());
+        assert_eq!(check(&answers, 3, 1, &ok("4")), Status::Pass);
+        assert_eq!(
+            check(&answers, 3, 1, &ok("5")),
+            Status::Fail("4".to_owned())
+        );
+        assert_eq!(
+            check(&answers, 3, 1, &Err(Error::new("bad"))),
+            Status::Fail("4".to_owned())
+        );
+        assert_eq!(check(&answers, 3, 2, &ok("5")), Status::Missing);
+        assert_eq!(check(&answers, 10, 1, &ok("\n*.\n.*")), Status::Pass);
+    }
+
+    #[test]
+    fn verify_report() {
+        let answers = Answers::parse("[day3]\npart1 = 4\n[day5]\npart1 = 6").unwrap();
+        let solved = run_day(
+            &find(3).unwrap(),
+            "#1 @ 1,3: 4x4\n#2 @ 3,1: 4x4\n#3 @ 5,5: 2x2",
+        );
+        let unparsed = Err(Error::new("bad").with_day(5));
+        assert_eq!(report(&answers, &[(3, solved), (5, unparsed)], &[1]), 1);
+        assert_eq!(report(&answers, &[], &[1, 2]), 0);
+    }
+}
@@ -0,0 +1,43 @@
+use advent_of_code2018::aoc12::OffsetVec;
+use advent_of_code2018::aoc16::{self, Day16};
+use advent_of_code2018::aoc8::Day8;
+use advent_of_code2018::asm;
+use advent_of_code2018::helper::i32_val;
+use advent_of_code2018::opcodes;
+use advent_of_code2018::runner::run_day;
+use advent_of_code2018::solution::{find, Solution};
+
+#[test]
+fn api_solution() {
+    let input = Day8::parse("2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2").unwrap();
+    assert_eq!(Day8::part_one(&input), Ok(138));
+    assert_eq!(Day8::part_two(&input), Ok(66));
+
+    let res = run_day(&find(8).unwrap(), "0 1 7").unwrap();
+    assert_eq!(res.part_one, Ok("7".to_owned()));
+}
+
+#[test]
+fn api_utilities() {
+    assert_eq!(i32_val("-12,"), Ok((",", -12)));
+
+    let mut regs = vec![3, 2, 1, 1];
+    opcodes::mulr(&mut regs, 2, 1, 2);
+    assert_eq!(regs, [3, 2, 2, 1]);
+
+    let mut v = OffsetVec::new(&[1, 2], 0);
+    v[-2] = 5;
+    assert_eq!((v.offset(), v.len()), (-2, 4));
+    assert_eq!((v[-2], v[0], v[10]), (5, 1, 0));
+}
+
+#[test]
+fn api_asm() {
+    let input = std::fs::read_to_string("inputs/day16.txt").unwrap();
+    let mapping = aoc16::mapping(&Day16::parse(&input).unwrap()).unwrap();
+    assert_eq!(mapping.len(), 16);
+
+    let listing = asm::translate("0 1 2 3\n", &mapping).unwrap();
+    let printed = listing.to_string();
+    assert_eq!(asm::parse(&printed).unwrap(), listing);
+}